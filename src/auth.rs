@@ -24,6 +24,14 @@ pub mod database {
         expirery: i64,
     }
 
+    #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+    pub enum Role {
+        #[default]
+        Inspector,
+        /// May change or void any inspection, set by editing `users.json`
+        Admin,
+    }
+
     #[derive(Serialize, Deserialize, Clone)]
     pub struct User {
         uuid: String,
@@ -32,6 +40,18 @@ pub mod database {
         password_hash: [u8; 24],
         salt: [u8; 16],
         pub tokens: Vec<Token>,
+        #[serde(default)]
+        pub role: Role,
+        #[serde(default = "crate::database::data::default_tenant")]
+        pub tenant: String,
+        /// Accounts made through signup cannot log in until an admin of their tenant approves
+        /// them, accounts made before approvals existed are approved
+        #[serde(default = "approved")]
+        pub approved: bool,
+    }
+
+    fn approved() -> bool {
+        true
     }
 
     pub enum UserError {
@@ -41,24 +61,20 @@ pub mod database {
     }
 
     impl User {
-        pub fn new(username: String, password: String) -> Result<Self, UserError> {
+        pub fn new(username: String, password: String, tenant: String) -> Result<Self, UserError> {
             use uuid::Uuid;
             // check if username_taken
-            let usernames = fs::read_to_string(USERNAMES_PATH.to_string())
+            let usernames = fs::read_to_string(USERNAMES_PATH)
                 .expect("We should always have this file available");
 
-            let username_taken: bool =
-                &usernames.split(",").filter(|&f| f == username).count() >= &1;
+            let username_taken: bool = usernames.split(',').filter(|&f| f == username).count() >= 1;
             match username_taken {
                 true => Err(UserError::UsernameDuplicate),
                 false => {
                     let salt: [u8; 16] = random();
 
-                    fs::write(
-                        USERNAMES_PATH.to_string(),
-                        format!("{},{}", usernames, username),
-                    )
-                    .expect("we should be able to write here at all times");
+                    fs::write(USERNAMES_PATH, format!("{},{}", usernames, username))
+                        .expect("we should be able to write here at all times");
 
                     Ok(User {
                         salt,
@@ -70,6 +86,9 @@ pub mod database {
                             hash
                         },
                         tokens: vec![],
+                        role: Role::default(),
+                        tenant,
+                        approved: false,
                     })
                 }
             }
@@ -93,7 +112,39 @@ pub mod database {
                     user = Some(u);
                 }
             }
-            return user;
+            user
+        }
+
+        pub fn find(uuid: &str) -> Option<Self> {
+            let users: Vec<User> = serde_json::de::from_str(
+                &fs::read_to_string("./database/auth_users/users.json")
+                    .expect("We should always get file here"),
+            )
+            .expect("we should always have a good json");
+
+            users.into_iter().find(|f| f.uuid == uuid)
+        }
+
+        /// Accounts in a tenant that are waiting for an admin to approve them
+        pub fn pending(tenant: &str) -> Vec<Self> {
+            let users: Vec<User> = serde_json::de::from_str(
+                &fs::read_to_string("./database/auth_users/users.json")
+                    .expect("We should always get file here"),
+            )
+            .expect("we should always have a good json");
+
+            users
+                .into_iter()
+                .filter(|f| !f.approved && f.tenant == tenant)
+                .collect()
+        }
+
+        pub fn uuid(&self) -> &str {
+            &self.uuid
+        }
+
+        pub fn username(&self) -> &str {
+            &self.username
         }
 
         // pub fn authenticate_user(username: String, password: String) -> bool {
@@ -123,7 +174,7 @@ pub mod database {
 
         pub fn push_to_disk(self) {
             let mut users: Vec<Self> = serde_json::from_str(
-                &fs::read_to_string("./database/auth_users/users.json")
+                fs::read_to_string("./database/auth_users/users.json")
                     .expect("Could not read users.json")
                     .as_str(),
             )
@@ -158,6 +209,25 @@ pub mod database {
             }
         }
 
+        /// The uuid of the authenticated user this token was issued to
+        pub fn user_uuid(&self) -> &str {
+            &self.uuid
+        }
+
+        fn get_user(&self) -> Option<User> {
+            User::find(&self.uuid)
+        }
+
+        /// The role of the user this token was issued to, `None` if the user no longer exists
+        pub fn role(&self) -> Option<Role> {
+            self.get_user().map(|f| f.role)
+        }
+
+        /// The tenant of the user this token was issued to, `None` if the user no longer exists
+        pub fn tenant(&self) -> Option<String> {
+            self.get_user().map(|f| f.tenant)
+        }
+
         pub fn check_token_validy(&self) -> TokenResponse {
             let users: Vec<User> = serde_json::de::from_str(
                 &fs::read_to_string("./database/auth_users/users.json")
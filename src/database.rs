@@ -3,17 +3,318 @@
 pub mod data {
     use std::fs;
 
-    use actix_web::HttpResponse;
     use serde::{Deserialize, Serialize};
     use uuid::Uuid;
 
+    /// The squadron that owned every record before the server hosted more than one
+    pub const DEFAULT_TENANT: &str = "default";
+
+    pub fn default_tenant() -> String {
+        DEFAULT_TENANT.into()
+    }
+
+    /// A squadron hosted on this server
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Tenant {
+        pub id: String,
+        pub name: String,
+        /// Where the front end for this squadron is served, used in QR codes and CORS
+        pub base_url: String,
+        #[serde(default)]
+        pub logo_url: Option<String>,
+        #[serde(default)]
+        pub colour: Option<String>,
+        /// Whether cadets can look up their own inspection history
+        #[serde(default = "enabled")]
+        pub cadet_view: bool,
+        /// After this timestamp QR codes holding a bare uuid are no longer accepted, only signed
+        /// card codes. Tenants without one are given [`LEGACY_QR_WINDOW`] at startup
+        #[serde(default)]
+        pub legacy_qr_until: Option<i64>,
+    }
+
+    fn enabled() -> bool {
+        true
+    }
+
+    /// Tenants are created by editing `database/tenants.json`, without it every record belongs to
+    /// the default tenant
+    pub fn load_tenants() -> Result<Vec<Tenant>, std::io::Error> {
+        match fs::read_to_string("./database/tenants.json") {
+            Ok(t) => Ok(serde_json::from_str(t.as_str())?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![Tenant {
+                id: default_tenant(),
+                name: "952 Air Cadets".into(),
+                base_url: "https://uniform.952aircadets.ca".into(),
+                logo_url: None,
+                colour: None,
+                cadet_view: true,
+                legacy_qr_until: None,
+            }]),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn write_tenants(tenants: &[Tenant]) -> Result<(), std::io::Error> {
+        fs::write("./database/tenants.json", serde_json::to_string(tenants)?)?;
+        refresh_tenant_origins()
+    }
+
+    /// Every tenant's `base_url`, kept in memory so CORS preflights do not read from disk
+    static TENANT_ORIGINS: std::sync::RwLock<Vec<String>> = std::sync::RwLock::new(vec![]);
+
+    /// Reloads the origins allowed through CORS, called at startup and whenever tenants are
+    /// written
+    pub fn refresh_tenant_origins() -> Result<(), std::io::Error> {
+        let origins = load_tenants()?.into_iter().map(|f| f.base_url).collect();
+        *TENANT_ORIGINS
+            .write()
+            .expect("Tenant origins lock poisoned") = origins;
+        Ok(())
+    }
+
+    pub fn is_tenant_origin(origin: &[u8]) -> bool {
+        TENANT_ORIGINS
+            .read()
+            .expect("Tenant origins lock poisoned")
+            .iter()
+            .any(|f| f.as_bytes() == origin)
+    }
+
+    pub fn find_tenant(id: &str) -> Result<Tenant, std::io::Error> {
+        load_tenants()?
+            .into_iter()
+            .find(|f| f.id == id)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Tenant not found"))
+    }
+
+    impl Tenant {
+        /// The link encoded in a cadet's QR code, see [`card_code`]
+        pub fn cadet_url(&self, code: &str) -> String {
+            format!("{}/u/{}", self.base_url, code)
+        }
+
+        /// The logo placed in the centre of QR codes, stored as `database/logos/{id}.png`
+        pub fn qr_logo(&self) -> Result<Option<Vec<u8>>, std::io::Error> {
+            match fs::read(format!("./database/logos/{}.png", self.id)) {
+                Ok(t) => Ok(Some(t)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+
+        /// Replaces the QR logo with an uploaded JPEG or PNG
+        pub fn set_qr_logo(&self, content_type: &str, bytes: &[u8]) -> Result<(), std::io::Error> {
+            let png = super::attachments::to_png(content_type, bytes, 512)?;
+
+            fs::create_dir_all("./database/logos")?;
+            fs::write(format!("./database/logos/{}.png", self.id), png)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum UnitKind {
+        Squadron,
+        Flight,
+        Section,
+    }
+
+    /// An organizational unit, cadets are assigned to a flight or section by its id
     #[derive(Serialize, Deserialize, Debug, Clone)]
-    pub enum Flight {
-        Beddoe,
-        Morgan,
-        Spear,
-        Bell,
-        Hill,
+    pub struct Unit {
+        #[serde(default)]
+        pub id: String,
+        pub name: String,
+        pub kind: UnitKind,
+        /// A flight belongs to a squadron and a section belongs to a flight
+        pub parent: Option<String>,
+        #[serde(default = "default_tenant")]
+        pub tenant: String,
+    }
+
+    /// Flights from before units were configurable, cadet records refer to them by name
+    const LEGACY_FLIGHTS: [&str; 5] = ["Beddoe", "Morgan", "Spear", "Bell", "Hill"];
+    const DEFAULT_SQUADRON: &str = "squadron";
+
+    impl Unit {
+        /// Checks that the parent exists and is the right kind of unit
+        pub fn validate_parent(&self, units: &[Unit]) -> Result<(), String> {
+            let parent = self
+                .parent
+                .as_ref()
+                .map(|p| units.iter().find(|f| &f.id == p).ok_or("Parent not found"))
+                .transpose()?;
+
+            match (self.kind, parent.map(|f| f.kind)) {
+                (UnitKind::Squadron, None)
+                | (UnitKind::Flight, Some(UnitKind::Squadron))
+                | (UnitKind::Section, Some(UnitKind::Flight)) => Ok(()),
+                _ => Err(format!("A {:?} cannot belong to that unit", self.kind)),
+            }
+        }
+    }
+
+    /// The flight a unit belongs to, a section rolls up into its flight. `None` for units above
+    /// flight level or units that no longer exist
+    pub fn flight_of(units: &[Unit], unit: &str) -> Option<String> {
+        units
+            .iter()
+            .filter(|f| f.kind == UnitKind::Flight)
+            .find(|f| unit_within(units, unit, &f.id))
+            .map(|f| f.id.clone())
+    }
+
+    /// Whether `unit` is `ancestor` or sits somewhere below it, e.g. a section within a flight
+    pub fn unit_within(units: &[Unit], unit: &str, ancestor: &str) -> bool {
+        let mut current = Some(unit.to_string());
+
+        // Bounded by the number of units in case the parents form a cycle
+        for _ in 0..=units.len() {
+            match current {
+                Some(t) if t == ancestor => return true,
+                Some(t) => {
+                    current = units
+                        .iter()
+                        .find(|f| f.id == t)
+                        .and_then(|f| f.parent.clone())
+                }
+                None => return false,
+            }
+        }
+
+        false
+    }
+
+    pub fn load_units() -> Result<Vec<Unit>, std::io::Error> {
+        match fs::read_to_string("./database/units.json") {
+            Ok(t) => Ok(serde_json::from_str(t.as_str())?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn load_tenant_units(tenant: &str) -> Result<Vec<Unit>, std::io::Error> {
+        Ok(load_units()?
+            .into_iter()
+            .filter(|f| f.tenant == tenant)
+            .collect())
+    }
+
+    pub fn write_units(units: &[Unit]) -> Result<(), std::io::Error> {
+        fs::write("./database/units.json", serde_json::to_string(units)?)?;
+        records_changed();
+        Ok(())
+    }
+
+    /// Creates the unit list from the old hardcoded flights the first time the server starts and
+    /// adds a flight for any cadet assigned to a unit that does not exist
+    pub fn migrate_units() -> Result<(), std::io::Error> {
+        let mut units = load_units()?;
+
+        if units.is_empty() {
+            units.push(Unit {
+                id: DEFAULT_SQUADRON.into(),
+                name: "Squadron".into(),
+                kind: UnitKind::Squadron,
+                parent: None,
+                tenant: default_tenant(),
+            });
+            units.extend(LEGACY_FLIGHTS.iter().map(|f| Unit {
+                id: f.to_string(),
+                name: f.to_string(),
+                kind: UnitKind::Flight,
+                parent: Some(DEFAULT_SQUADRON.into()),
+                tenant: default_tenant(),
+            }));
+        }
+
+        for user in read_all_users()? {
+            let Some(flight) = user.flight else {
+                continue;
+            };
+
+            if !units.iter().any(|f| f.id == flight) {
+                let squadron = units
+                    .iter()
+                    .find(|f| f.kind == UnitKind::Squadron && f.tenant == user.tenant)
+                    .map(|f| f.id.clone());
+
+                units.push(Unit {
+                    id: flight.clone(),
+                    name: flight,
+                    kind: UnitKind::Flight,
+                    parent: squadron,
+                    tenant: user.tenant,
+                });
+            }
+        }
+
+        write_units(&units)
+    }
+
+    /// How long bare uuid QR codes keep working once signed card codes are in use, 90 days
+    pub const LEGACY_QR_WINDOW: i64 = 90 * 24 * 60 * 60;
+
+    /// Ends the bare uuid transition for tenants that have no end set, so old cards stop working
+    /// after [`LEGACY_QR_WINDOW`] unless an admin moves the date in `database/tenants.json`
+    pub fn migrate_legacy_qr_windows() -> Result<(), std::io::Error> {
+        let mut tenants = load_tenants()?;
+        let until = chrono::Utc::now().timestamp() + LEGACY_QR_WINDOW;
+        let mut changed = false;
+
+        for tenant in tenants.iter_mut().filter(|f| f.legacy_qr_until.is_none()) {
+            log::warn!(
+                "Tenant {} had no end for bare uuid QR codes, they will stop working at {}",
+                tenant.id,
+                until
+            );
+            tenant.legacy_qr_until = Some(until);
+            changed = true;
+        }
+
+        match changed {
+            true => write_tenants(&tenants),
+            false => Ok(()),
+        }
+    }
+
+    /// Issues a claim code to every unclaimed cadet created before claim codes existed
+    pub fn migrate_claim_codes() -> Result<(), std::io::Error> {
+        for mut user in read_all_users()? {
+            if user.username.is_some() || user.claim_code.is_some() {
+                continue;
+            }
+
+            user.issue_claim_code();
+            fs::write(
+                format!("./database/users/{}.json", user.uuid),
+                serde_json::to_string(&user)?,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Gives an id to every inspection recorded before inspections had ids, so attachments,
+    /// amendments and voids can refer to them
+    pub fn migrate_inspection_ids() -> Result<(), std::io::Error> {
+        for mut user in read_all_users()? {
+            if user.inspections.iter().all(|f| f.id.is_some()) {
+                continue;
+            }
+
+            user.inspections
+                .iter_mut()
+                .filter(|f| f.id.is_none())
+                .for_each(|f| f.id = Some(Uuid::new_v4().to_string()));
+
+            fs::write(
+                format!("./database/users/{}.json", user.uuid),
+                serde_json::to_string(&user)?,
+            )?;
+        }
+
+        Ok(())
     }
 
     #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -23,11 +324,28 @@ pub mod data {
         Comment(Option<String>),
     }
 
+    /// Marks a criterion as not counting towards an inspection, it is removed from both `score`
+    /// and `out_of`
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Exemption {
+        /// The criterion does not apply to this cadet (e.g. a badge they have not earned)
+        NotApplicable,
+        /// The criterion applies but the cadet has been excused from it
+        Excused,
+    }
+
     #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
     pub struct CriteriaPassFail {
         pub category_name: String,
         pub description: String,
         pub state: Option<bool>,
+        #[serde(default)]
+        pub exemption: Option<Exemption>,
+        #[serde(default)]
+        pub note: Option<String>,
+        /// Codes from the deficiency catalogue, see [`load_deficiency_codes`]
+        #[serde(default)]
+        pub deficiency_codes: Vec<String>,
     }
 
     #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -37,109 +355,508 @@ pub mod data {
         pub category_name: String,
         pub description: Vec<String>,
         pub state: Option<u8>,
+        #[serde(default)]
+        pub exemption: Option<Exemption>,
+        #[serde(default)]
+        pub note: Option<String>,
+        /// Codes from the deficiency catalogue, see [`load_deficiency_codes`]
+        #[serde(default)]
+        pub deficiency_codes: Vec<String>,
+    }
+
+    impl Criteria {
+        pub fn exemption(&self) -> Option<Exemption> {
+            match self {
+                Criteria::PassFail(t) => t.exemption,
+                Criteria::Graded(t) => t.exemption,
+                Criteria::Comment(_) => None,
+            }
+        }
+
+        pub fn deficiency_codes(&self) -> &[String] {
+            match self {
+                Criteria::PassFail(t) => &t.deficiency_codes,
+                Criteria::Graded(t) => &t.deficiency_codes,
+                Criteria::Comment(_) => &[],
+            }
+        }
+
+        pub fn category_name(&self) -> Option<&str> {
+            match self {
+                Criteria::PassFail(t) => Some(&t.category_name),
+                Criteria::Graded(t) => Some(&t.category_name),
+                Criteria::Comment(_) => None,
+            }
+        }
+
+        /// Whether the cadet fell short of full marks, `None` for comments and exempt criteria
+        pub fn failed(&self) -> Option<bool> {
+            match (self, self.exemption()) {
+                (Criteria::Comment(_), _) | (_, Some(_)) => None,
+                _ => Some(self.score() < self.out_of()),
+            }
+        }
+
+        /// The points this criterion is worth, exempt criteria are worth nothing
+        pub fn out_of(&self) -> u16 {
+            match (self, self.exemption()) {
+                (_, Some(_)) => 0,
+                (Criteria::Graded(t), None) => t.description.len() as u16,
+                (Criteria::PassFail(_), None) => 1,
+                (Criteria::Comment(_), None) => 0,
+            }
+        }
+
+        /// The points awarded for this criterion, unanswered criteria count as a fail
+        pub fn score(&self) -> u16 {
+            match (self, self.exemption()) {
+                (_, Some(_)) => 0,
+                (Criteria::Graded(t), None) => t.state.unwrap_or(0).into(),
+                (Criteria::PassFail(t), None) => match t.state {
+                    Some(true) => 1,
+                    Some(false) | None => 0,
+                },
+                (Criteria::Comment(_), None) => 0,
+            }
+        }
     }
 
     #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
     pub struct Inspection {
+        /// Assigned when the inspection is recorded against a cadet
+        #[serde(default)]
+        pub id: Option<String>,
+        /// Restricts a template in `inspections.json` to one tenant, `None` is shared by all
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub tenant: Option<String>,
+        /// Restricts a template to cadets holding one of these ranks, empty applies to all ranks
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub ranks: Vec<String>,
+        /// The scheduled event this inspection was recorded during
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub event: Option<String>,
         pub name: String,
         pub criteria: Vec<Criteria>,
         pub date: Option<i64>,
         pub out_of: Option<u16>,
         pub score: Option<u16>,
+        #[serde(default)]
+        pub attachments: Vec<Attachment>,
+        /// uuid of the authenticated user that recorded the inspection
+        #[serde(default)]
+        pub inspector: Option<String>,
+        /// Voided inspections are kept for auditing but no longer count towards a cadet's scores
+        #[serde(default)]
+        pub voided: Option<Void>,
+        /// Prior versions of this inspection, oldest first
+        #[serde(default)]
+        pub history: Vec<InspectionRevision>,
     }
 
     impl Default for Inspection {
         fn default() -> Self {
             Self {
+                id: None,
+                tenant: None,
+                ranks: vec![],
+                event: None,
                 name: "".into(),
                 criteria: vec![],
                 date: None,
                 out_of: None,
                 score: None,
+                attachments: vec![],
+                inspector: None,
+                voided: None,
+                history: vec![],
             }
         }
     }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+    pub struct Void {
+        pub voided_by: String,
+        pub reason: String,
+        pub voided_at: i64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+    pub struct InspectionRevision {
+        /// The inspection as it was before this revision, without its own history
+        pub previous: Inspection,
+        pub revised_by: String,
+        pub reason: String,
+        pub revised_at: i64,
+    }
+
+    /// A photo attached to an inspection, the image itself is stored by [`super::attachments`]
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+    pub struct Attachment {
+        pub id: String,
+        /// The criterion the photo documents, `None` if it applies to the whole inspection
+        pub category_name: Option<String>,
+        pub content_type: String,
+        /// uuid of the authenticated user that uploaded the photo
+        pub uploaded_by: String,
+        pub uploaded_at: i64,
+    }
+    /// A step on a tenant's rank ladder, lower `order` is more junior
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Rank {
+        /// Short form stored on cadets, e.g. `FCpl`
+        pub id: String,
+        pub name: String,
+        pub order: u8,
+        #[serde(default = "default_tenant")]
+        pub tenant: String,
+    }
+
+    const DEFAULT_RANKS: [(&str, &str); 8] = [
+        ("AC", "Air Cadet"),
+        ("LAC", "Leading Air Cadet"),
+        ("Cpl", "Corporal"),
+        ("FCpl", "Flight Corporal"),
+        ("Sgt", "Sergeant"),
+        ("FSgt", "Flight Sergeant"),
+        ("WO2", "Warrant Officer Second Class"),
+        ("WO1", "Warrant Officer First Class"),
+    ];
+
+    fn load_all_ranks() -> Result<Vec<Rank>, std::io::Error> {
+        match fs::read_to_string("./database/ranks.json") {
+            Ok(t) => Ok(serde_json::from_str(t.as_str())?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// A tenant's rank ladder ordered from most junior, tenants that have not configured one use
+    /// the air cadet ranks
+    pub fn load_ranks(tenant: &str) -> Result<Vec<Rank>, std::io::Error> {
+        let mut ranks: Vec<Rank> = load_all_ranks()?
+            .into_iter()
+            .filter(|f| f.tenant == tenant)
+            .collect();
+
+        if ranks.is_empty() {
+            ranks = DEFAULT_RANKS
+                .iter()
+                .enumerate()
+                .map(|(order, (id, name))| Rank {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    order: order as u8,
+                    tenant: tenant.into(),
+                })
+                .collect();
+        }
+        ranks.sort_by_key(|f| f.order);
+
+        Ok(ranks)
+    }
+
+    /// Replaces a tenant's rank ladder
+    pub fn write_ranks(tenant: &str, ranks: Vec<Rank>) -> Result<(), std::io::Error> {
+        let mut all_ranks = load_all_ranks()?;
+        all_ranks.retain(|f| f.tenant != tenant);
+        all_ranks.extend(ranks.into_iter().map(|f| Rank {
+            tenant: tenant.into(),
+            ..f
+        }));
+
+        fs::write("./database/ranks.json", serde_json::to_string(&all_ranks)?)
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Promotion {
+        pub rank: String,
+        pub previous_rank: Option<String>,
+        pub date: i64,
+        /// uuid of the authenticated user that approved the promotion
+        pub approved_by: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum CadetStatus {
+        #[default]
+        Active,
+        Inactive,
+        Graduated,
+    }
+
+    /// Details about a cadet maintained by staff
+    #[derive(Serialize, Deserialize, Debug, Clone, Default)]
+    #[serde(default)]
+    pub struct Profile {
+        pub first_name: Option<String>,
+        pub last_name: Option<String>,
+        pub rank: Option<String>,
+        pub service_number: Option<String>,
+        pub date_joined: Option<i64>,
+        pub status: CadetStatus,
+        /// Internal notes, only for staff
+        pub notes: Option<String>,
+    }
+
+    /// Generates a short code that is easy to read off a printed card
+    fn new_claim_code() -> String {
+        use rand::Rng;
+
+        // Leaves out characters that are easily confused, e.g. 0 and O
+        const ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+
+        let mut rng = rand::thread_rng();
+        (0..6)
+            .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+            .collect()
+    }
+
+    /// Longest chain of merge redirects followed before a record is treated as broken
+    const MAX_REDIRECTS: usize = 8;
+
+    /// Wrong claim codes allowed before the code is locked and staff have to issue a new one
+    pub const MAX_CLAIM_ATTEMPTS: u32 = 5;
+
     #[derive(Serialize, Deserialize, Debug, Clone)]
     pub struct User {
         // User Info
         pub username: Option<String>,
         pub uuid: String,
         pub inspections: Vec<Inspection>,
-        pub flight: Option<Flight>,
+        /// Id of the flight or section the cadet belongs to, see [`Unit`]
+        pub flight: Option<String>,
         pub dev_user: bool,
+        #[serde(default = "default_tenant")]
+        pub tenant: String,
+        #[serde(flatten)]
+        pub profile: Profile,
+        /// Oldest first
+        #[serde(default)]
+        pub promotions: Vec<Promotion>,
+        /// When the cadet was archived, archived cadets are hidden from the index by default
+        #[serde(default)]
+        pub archived: Option<i64>,
+        /// Set on a duplicate record after it has been merged, reads are redirected to this uuid
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub merged_into: Option<String>,
+        /// One-time code printed with the QR card, lets the cadet claim the record without staff
+        #[serde(default)]
+        pub claim_code: Option<String>,
+        /// A claim made without a code, waiting for staff approval
+        #[serde(default)]
+        pub pending_claim: Option<String>,
+        /// Wrong claim codes entered since the code was issued, see [`MAX_CLAIM_ATTEMPTS`]
+        #[serde(default)]
+        pub claim_attempts: u32,
+        /// Bumped when a card is lost, codes signed for an earlier generation stop working
+        #[serde(default)]
+        pub card_generation: u32,
+        /// At most one record per event
+        #[serde(default)]
+        pub attendance: Vec<Attendance>,
     }
 
-    impl Inspection {
-        pub fn compute_score(self: &mut Self) {
-            let true_false_map = |x: Option<bool>| match x {
-                Some(true) => 1,
-                Some(false) | None => 0,
-            };
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum AttendanceStatus {
+        Present,
+        Absent,
+        /// Not counted towards the attendance percentage
+        Excused,
+        Late,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Attendance {
+        pub event: String,
+        pub status: AttendanceStatus,
+        pub recorded_by: String,
+        pub recorded_at: i64,
+    }
 
-            let out_of_map = |x: &Criteria| -> u16 {
-                match x {
-                    Criteria::Graded(t) => t.description.len() as u16,
-                    Criteria::PassFail(_) => 1,
-                    Criteria::Comment(_) => 0,
+    impl Inspection {
+        /// Checks that graded criteria are within their rubric and that every deficiency code is
+        /// in the catalogue, returning a message describing the first problem found
+        pub fn validate(&self, catalogue: &[DeficiencyCode]) -> Result<(), String> {
+            for criteria in self.criteria.iter() {
+                if let Criteria::Graded(t) = criteria {
+                    if t.state.unwrap_or(0) as usize > t.description.len() {
+                        return Err(format!("Grade out of range for {}", t.category_name));
+                    }
                 }
-            };
-            let score_map = |x: &Criteria| -> u16 {
-                match x {
-                    Criteria::Graded(t) => t.state.unwrap_or(0).into(),
-                    Criteria::PassFail(t) => true_false_map(t.state),
-                    Criteria::Comment(_) => 0,
+
+                if let Some(code) = criteria
+                    .deficiency_codes()
+                    .iter()
+                    .find(|code| !catalogue.iter().any(|f| &f.code == *code))
+                {
+                    return Err(format!("Unknown deficiency code {code}"));
                 }
-            };
+            }
 
-            self.score = Some(self.criteria.iter().map(score_map).sum());
-            self.out_of = Some(self.criteria.iter().map(out_of_map).sum());
+            Ok(())
         }
 
-        pub fn get_score(self: &Self) -> InspectionScore {
-            let true_false_map = |x: Option<bool>| match x {
-                Some(true) => 1,
-                Some(false) | None => 0,
-            };
+        /// Replaces the recorded criteria, keeping the current version in the history. Voided
+        /// inspections cannot be amended
+        pub fn amend(
+            &mut self,
+            amended: Inspection,
+            revised_by: String,
+            reason: String,
+        ) -> Result<(), String> {
+            if self.voided.is_some() {
+                return Err("A voided inspection cannot be amended".into());
+            }
 
-            let out_of_map = |x: &Criteria| -> u16 {
-                match x {
-                    Criteria::Graded(t) => t.description.len() as u16,
-                    Criteria::PassFail(_) => 1,
-                    Criteria::Comment(_) => 0,
-                }
-            };
-            let score_map = |x: &Criteria| -> u16 {
-                match x {
-                    Criteria::Graded(t) => t.state.unwrap_or(0).into(),
-                    Criteria::PassFail(t) => true_false_map(t.state),
-                    Criteria::Comment(_) => 0,
-                }
-            };
+            let mut previous = self.clone();
+            previous.history = vec![];
+            previous.attachments = vec![];
+
+            self.history.push(InspectionRevision {
+                previous,
+                revised_by,
+                reason,
+                revised_at: chrono::Utc::now().timestamp(),
+            });
+
+            self.name = amended.name;
+            self.criteria = amended.criteria;
+            if amended.date.is_some() {
+                self.date = amended.date;
+            }
+            self.compute_score();
+            Ok(())
+        }
+
+        /// Marks the inspection as voided, an inspection can only be voided once so the original
+        /// void record is never lost
+        pub fn void(&mut self, voided_by: String, reason: String) -> Result<(), String> {
+            if self.voided.is_some() {
+                return Err("Inspection is already voided".into());
+            }
+
+            self.voided = Some(Void {
+                voided_by,
+                reason,
+                voided_at: chrono::Utc::now().timestamp(),
+            });
+            Ok(())
+        }
 
-            let score = self.criteria.iter().map(score_map).sum();
-            let out_of = self.criteria.iter().map(out_of_map).sum();
+        pub fn compute_score(&mut self) {
+            let score = self.get_score();
 
-            InspectionScore { score, out_of }
+            self.score = Some(score.score);
+            self.out_of = Some(score.out_of);
+        }
+
+        pub fn get_score(&self) -> InspectionScore {
+            let count_exempt = |exemption: Exemption| {
+                self.criteria
+                    .iter()
+                    .filter(|f| f.exemption() == Some(exemption))
+                    .count() as u16
+            };
+
+            InspectionScore {
+                score: self.criteria.iter().map(Criteria::score).sum(),
+                out_of: self.criteria.iter().map(Criteria::out_of).sum(),
+                not_applicable: count_exempt(Exemption::NotApplicable),
+                excused: count_exempt(Exemption::Excused),
+            }
         }
     }
 
-    #[derive(Debug, Deserialize, Serialize)]
+    #[derive(Debug, Deserialize, Serialize, Clone)]
     pub struct InspectionScore {
         score: u16,
         out_of: u16,
+        /// Number of criteria marked as not applicable, these are not counted in `out_of`
+        #[serde(default)]
+        not_applicable: u16,
+        /// Number of criteria the cadet was excused from, these are not counted in `out_of`
+        #[serde(default)]
+        excused: u16,
+    }
+
+    impl InspectionScore {
+        pub fn score(&self) -> u16 {
+            self.score
+        }
+
+        pub fn out_of(&self) -> u16 {
+            self.out_of
+        }
+
+        /// The score as a percentage of `out_of`, `None` when nothing was scored
+        pub fn percentage(&self) -> Option<f32> {
+            match self.out_of {
+                0 => None,
+                out_of => Some(self.score as f32 / out_of as f32 * 100.0),
+            }
+        }
     }
 
     impl User {
+        /// Inspections that have not been voided
+        pub fn counted_inspections(&self) -> impl DoubleEndedIterator<Item = &Inspection> {
+            self.inspections.iter().filter(|f| f.voided.is_none())
+        }
+
         fn get_latest_inspection_date(&self) -> Option<i64> {
-            self.inspections.last()?.date
+            self.counted_inspections().last()?.date
         }
 
         fn get_latest_inspection_score(&self) -> Option<InspectionScore> {
-            Some(self.inspections.last()?.get_score())
+            Some(self.counted_inspections().last()?.get_score())
+        }
+
+        /// "Last, First" when the profile has a name, otherwise the username or uuid
+        pub fn display_name(&self) -> String {
+            match (&self.profile.last_name, &self.profile.first_name) {
+                (Some(last), Some(first)) => format!("{last}, {first}"),
+                (Some(t), None) | (None, Some(t)) => t.clone(),
+                (None, None) => self.username.clone().unwrap_or_else(|| self.uuid.clone()),
+            }
+        }
+
+        /// Records attendance for an event, replacing any earlier record for the same event
+        pub fn mark_attendance(
+            &mut self,
+            event: &str,
+            status: AttendanceStatus,
+            recorded_by: &str,
+        ) {
+            self.attendance.retain(|f| f.event != event);
+            self.attendance.push(Attendance {
+                event: event.to_string(),
+                status,
+                recorded_by: recorded_by.to_string(),
+                recorded_at: chrono::Utc::now().timestamp(),
+            });
+        }
+
+        /// Share of events the cadet attended, late counts as attended and excused absences are
+        /// left out. `None` when there is nothing to count
+        pub fn attendance_percentage(&self) -> Option<f32> {
+            let counted: Vec<&Attendance> = self
+                .attendance
+                .iter()
+                .filter(|f| f.status != AttendanceStatus::Excused)
+                .collect();
+            let attended = counted
+                .iter()
+                .filter(|f| matches!(f.status, AttendanceStatus::Present | AttendanceStatus::Late))
+                .count();
+
+            match counted.len() {
+                0 => None,
+                n => Some(attended as f32 / n as f32 * 100.0),
+            }
         }
 
-        pub fn new() -> User {
-            let new_user = User {
+        pub fn new(tenant: String) -> User {
+            User {
                 username: None,
                 uuid: Uuid::new_v4().to_string(),
                 inspections: Vec::new(),
@@ -147,35 +864,135 @@ pub mod data {
                 // REMOVE THIS FLAG LATER
                 dev_user: false,
                 // REMOVE THIS FLAG LATER
-            };
-            new_user
+                tenant,
+                profile: Profile::default(),
+                promotions: vec![],
+                archived: None,
+                merged_into: None,
+                claim_code: Some(new_claim_code()),
+                claim_attempts: 0,
+                pending_claim: None,
+                card_generation: 0,
+                attendance: vec![],
+            }
         }
         pub fn push_to_data_base(&self) {
+            self.write_to_disk().expect("failed to write to disk");
+
+            index_users().expect("Failed to index");
+        }
+
+        /// Writes the cadet without re-indexing, for bulk changes that call [`index_users`] once
+        /// when they are done
+        pub fn write_to_disk(&self) -> Result<(), std::io::Error> {
             fs::write(
                 format!("database/users/{}.json", &self.uuid),
-                serde_json::ser::to_string(&self).expect("Failed to serilize user"),
+                serde_json::ser::to_string(&self)?,
             )
-            .expect("failed to write to disk");
+        }
+        /// Moves another cadet's inspections, promotions, attendance and photos into this cadet,
+        /// filling in any profile fields this cadet is missing. `other` is left as a redirect to this cadet.
+        pub fn merge(&mut self, other: &mut User) -> Result<(), std::io::Error> {
+            super::attachments::move_all(&other.uuid, &self.uuid)?;
+            Draft::delete_for_user(&other.uuid)?;
 
-            index_users().expect("Failed to index");
+            self.inspections.append(&mut other.inspections);
+            self.promotions.append(&mut other.promotions);
+            self.promotions.sort_by_key(|f| f.date);
+            for record in other.attendance.drain(..) {
+                if !self.attendance.iter().any(|f| f.event == record.event) {
+                    self.attendance.push(record);
+                }
+            }
+
+            self.username = self.username.take().or(other.username.take());
+            if self.username.is_some() {
+                self.claim_code = None;
+                self.pending_claim = None;
+            }
+            other.claim_code = None;
+            other.pending_claim = None;
+            self.flight = self.flight.take().or(other.flight.take());
+
+            let (profile, other_profile) = (&mut self.profile, std::mem::take(&mut other.profile));
+            profile.first_name = profile.first_name.take().or(other_profile.first_name);
+            profile.last_name = profile.last_name.take().or(other_profile.last_name);
+            profile.rank = profile.rank.take().or(other_profile.rank);
+            profile.service_number = profile
+                .service_number
+                .take()
+                .or(other_profile.service_number);
+            profile.date_joined = match (profile.date_joined, other_profile.date_joined) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            profile.notes = match (profile.notes.take(), other_profile.notes) {
+                (Some(a), Some(b)) => Some(format!("{a}\n{b}")),
+                (a, b) => a.or(b),
+            };
+
+            other.merged_into = Some(self.uuid.clone());
+            Ok(())
         }
+
+        /// Permanently removes the cadet along with their drafts and attachments, used for
+        /// privacy requests
+        pub fn delete_from_database(self) -> Result<(), std::io::Error> {
+            super::attachments::delete_for_user(&self.uuid)?;
+            Draft::delete_for_user(&self.uuid)?;
+            fs::remove_file(format!("database/users/{}.json", &self.uuid))?;
+
+            index_users()?;
+            Ok(())
+        }
+
         pub fn push_inspection(&mut self, inspec: Inspection) {
             let mut inspect = inspec;
             if inspect.date.is_none() {
                 inspect.date = Some(chrono::Utc::now().timestamp())
             }
+            inspect.id = Some(Uuid::new_v4().to_string());
+            inspect.tenant = None;
+            inspect.ranks = vec![];
+            inspect.attachments = vec![];
+            inspect.voided = None;
+            inspect.history = vec![];
 
             inspect.compute_score();
 
             self.inspections.push(inspect);
         }
-        pub fn read_from_database(uuid: String) -> Result<User, HttpResponse> {
-            let mut user: User = serde_json::de::from_str(
-                fs::read_to_string(format!("./database/users/{}.json", uuid))
-                    .map_err(|_| actix_web::HttpResponse::NotFound())?
-                    .as_str(),
-            )
-            .map_err(|_| actix_web::HttpResponse::NotFound())?;
+        /// Reads the record stored under `uuid` as it is, without following a merge redirect
+        fn read_stored(uuid: &str) -> Result<User, std::io::Error> {
+            Ok(serde_json::de::from_str(
+                fs::read_to_string(format!("./database/users/{}.json", uuid))?.as_str(),
+            )?)
+        }
+
+        /// Follows merge redirects so old QR codes keep working
+        pub fn read_from_database(uuid: String) -> Result<User, std::io::Error> {
+            let mut uuid = uuid;
+            let mut user: User;
+
+            // Bounded in case a bad merge left a cycle
+            let mut redirects = 0;
+            loop {
+                user = Self::read_stored(&uuid)?;
+
+                match user.merged_into.take() {
+                    Some(t) if redirects < MAX_REDIRECTS => {
+                        uuid = t;
+                        redirects += 1;
+                    }
+                    Some(_) => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            "Too many merge redirects",
+                        ))
+                    }
+                    None => break,
+                }
+            }
 
             let mut inspections = user.inspections;
 
@@ -187,98 +1004,2707 @@ pub mod data {
 
             Ok(user)
         }
-    }
 
-    pub fn load_inspection_list() -> Result<Vec<Inspection>, std::io::Error> {
-        let inspection_lists: Vec<Inspection> =
-            serde_json::from_str(fs::read_to_string("./database/inspections.json")?.as_str())
-                .expect("Invalid Yaml in Inspection List");
+        /// Reads a cadet, treating cadets belonging to another tenant as not found
+        pub fn read_for_tenant(uuid: String, tenant: &str) -> Result<User, std::io::Error> {
+            let user = Self::read_from_database(uuid)?;
 
-        Ok(inspection_lists)
-    }
+            match user.tenant == tenant {
+                true => Ok(user),
+                false => Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "User not found",
+                )),
+            }
+        }
 
-    #[derive(Serialize, Deserialize, Debug)]
-    pub struct FlightIndexItem {
-        user_uuid: String,
-        flight: Option<Flight>,
-        name: Option<String>,
-        latest_inspection_date: Option<i64>,
-        latest_inspection_score: Option<InspectionScore>,
-    }
+        /// Records the promotion in date order, the cadet's rank only changes if it is the most
+        /// recent promotion
+        pub fn promote(&mut self, rank: String, date: i64, approved_by: String) {
+            self.promotions.sort_by_key(|f| f.date);
 
-    impl From<&User> for FlightIndexItem {
-        fn from(value: &User) -> Self {
-            let value = value.clone();
-            let latest_inspection_date = value.get_latest_inspection_date();
-            let latest_inspection_score = value.get_latest_inspection_score();
-            Self {
-                user_uuid: value.uuid,
-                name: value.username,
-                flight: value.flight,
-                latest_inspection_date,
-                latest_inspection_score,
+            // Promotions on the same date are kept in the order they were recorded
+            let position = self.promotions.partition_point(|f| f.date <= date);
+            let previous_rank = match position {
+                0 => match self.promotions.first() {
+                    Some(t) => t.previous_rank.clone(),
+                    None => self.profile.rank.clone(),
+                },
+                t => Some(self.promotions[t - 1].rank.clone()),
+            };
+
+            match self.promotions.get_mut(position) {
+                Some(next) => next.previous_rank = Some(rank.clone()),
+                None => self.profile.rank = Some(rank.clone()),
             }
+
+            self.promotions.insert(
+                position,
+                Promotion {
+                    rank,
+                    previous_rank,
+                    date,
+                    approved_by,
+                },
+            );
+        }
+
+        /// Replaces the claim code and clears any failed attempts, returning the new code
+        pub fn issue_claim_code(&mut self) -> String {
+            let code = new_claim_code();
+            self.claim_code = Some(code.clone());
+            self.claim_attempts = 0;
+            code
+        }
+
+        pub fn find_inspection_mut(&mut self, id: &str) -> Option<&mut Inspection> {
+            self.inspections
+                .iter_mut()
+                .find(|f| f.id.as_deref() == Some(id))
         }
     }
 
-    impl From<User> for FlightIndexItem {
-        fn from(value: User) -> Self {
-            let last_inspection = value.get_latest_inspection_date();
-            let latest_inspection_score = value.get_latest_inspection_score();
+    static CARD_KEY: std::sync::OnceLock<Vec<u8>> = std::sync::OnceLock::new();
+
+    /// Loads the key card codes are signed with, taken from `QR_SECRET` or generated once and kept
+    /// in `database/qr_secret`, readable only by the server's user
+    pub fn init_card_key() -> Result<(), std::io::Error> {
+        let key = match std::env::var("QR_SECRET") {
+            Ok(t) if t.is_empty() => {
+                return Err(std::io::Error::other("QR_SECRET must not be empty"));
+            }
+            Ok(t) => t.into_bytes(),
+            Err(_) => match fs::read("./database/qr_secret") {
+                Ok(t) if t.is_empty() => {
+                    return Err(std::io::Error::other("database/qr_secret is empty"));
+                }
+                Ok(t) => t,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    use std::io::Write;
+
+                    let key: [u8; 32] = rand::random();
+                    let mut options = fs::OpenOptions::new();
+                    options.write(true).create_new(true);
+                    #[cfg(unix)]
+                    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+                    options.open("./database/qr_secret")?.write_all(&key)?;
+                    key.to_vec()
+                }
+                Err(e) => return Err(e),
+            },
+        };
+
+        // Already loaded, the key can't change while the server is running
+        let _ = CARD_KEY.set(key);
+        Ok(())
+    }
+
+    fn card_key() -> &'static [u8] {
+        CARD_KEY
+            .get()
+            .expect("The card key is loaded at startup by init_card_key")
+    }
+
+    fn card_signature(uuid: &str, generation: u32) -> Vec<u8> {
+        use crypto::mac::Mac;
+
+        let mut hmac = crypto::hmac::Hmac::new(crypto::sha2::Sha256::new(), card_key());
+        hmac.input(format!("{uuid}.{generation}").as_bytes());
+
+        // Half the digest keeps the QR code small while staying far beyond guessing
+        hmac.result().code()[..16].to_vec()
+    }
+
+    /// What a cadet's QR code holds, `{uuid}.{generation}.{signature}`
+    pub fn card_code(user: &User) -> String {
+        let signature: String = card_signature(&user.uuid, user.card_generation)
+            .iter()
+            .map(|f| format!("{f:02x}"))
+            .collect();
+
+        format!("{}.{}.{}", user.uuid, user.card_generation, signature)
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum CardError {
+        /// Malformed, forged or for a cadet that does not exist
+        Invalid,
+        /// Signed for an earlier card generation, the card has been replaced
+        Revoked,
+        /// A bare uuid after the tenant's transition window closed
+        Expired,
+    }
+
+    /// What a scanned QR code claims to be, see [`resolve_card`]
+    #[derive(Debug, PartialEq, Eq)]
+    enum CardClaim<'a> {
+        /// A code made by [`card_code`], its signature has been checked
+        Signed { uuid: &'a str, generation: u32 },
+        /// A bare uuid, as printed on cards before codes were signed
+        Bare(&'a str),
+    }
+
+    impl<'a> CardClaim<'a> {
+        fn uuid(&self) -> &'a str {
+            match self {
+                CardClaim::Signed { uuid, .. } | CardClaim::Bare(uuid) => uuid,
+            }
+        }
+    }
+
+    /// Whether the code is signed, bare uuids are only accepted while old cards are phased out
+    pub fn card_is_signed(code: &str) -> bool {
+        matches!(parse_card(code), Ok(CardClaim::Signed { .. }))
+    }
+
+    /// Splits a code into its parts, rejecting malformed codes and forged signatures
+    fn parse_card(code: &str) -> Result<CardClaim<'_>, CardError> {
+        let parts: Vec<&str> = code.trim().split('.').collect();
+        match parts[..] {
+            [uuid, generation, signature] => {
+                let generation: u32 = generation.parse().map_err(|_| CardError::Invalid)?;
+                let signature: Vec<u8> = (0..signature.len())
+                    .step_by(2)
+                    .map(|f| u8::from_str_radix(signature.get(f..f + 2).unwrap_or(""), 16))
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| CardError::Invalid)?;
+                if !crypto::util::fixed_time_eq(&signature, &card_signature(uuid, generation)) {
+                    return Err(CardError::Invalid);
+                }
+
+                Ok(CardClaim::Signed { uuid, generation })
+            }
+            [uuid] if Uuid::parse_str(uuid).is_ok() => Ok(CardClaim::Bare(uuid)),
+            _ => Err(CardError::Invalid),
+        }
+    }
+
+    /// Checks a claim against the record stored under its uuid. This is done before merge
+    /// redirects are followed, so cards printed for a merged duplicate are checked against the
+    /// duplicate's own card generation.
+    fn check_card(
+        claim: &CardClaim,
+        stored: &User,
+        legacy_qr_until: Option<i64>,
+        now: i64,
+    ) -> Result<(), CardError> {
+        match claim {
+            CardClaim::Signed { generation, .. } if *generation != stored.card_generation => {
+                Err(CardError::Revoked)
+            }
+            CardClaim::Signed { .. } => Ok(()),
+            CardClaim::Bare(_) if legacy_qr_until.is_some_and(|t| now >= t) => {
+                Err(CardError::Expired)
+            }
+            CardClaim::Bare(_) if stored.card_generation > 0 => Err(CardError::Revoked),
+            CardClaim::Bare(_) => Ok(()),
+        }
+    }
+
+    /// Finds the cadet a scanned QR code belongs to. Signed codes must match the current card
+    /// generation, bare uuids from older cards are accepted until the tenant's `legacy_qr_until`
+    /// unless the card has since been replaced.
+    pub fn resolve_card(code: &str) -> Result<User, CardError> {
+        let claim = parse_card(code)?;
+        let stored = User::read_stored(claim.uuid()).map_err(|_| CardError::Invalid)?;
+        let tenant = find_tenant(&stored.tenant).map_err(|_| CardError::Invalid)?;
+        check_card(
+            &claim,
+            &stored,
+            tenant.legacy_qr_until,
+            chrono::Utc::now().timestamp(),
+        )?;
+
+        User::read_from_database(claim.uuid().to_string()).map_err(|_| CardError::Invalid)
+    }
+
+    /// Replaces a lost card. The cadet and every duplicate merged into them, directly or through
+    /// another duplicate, move to a new card generation so no card printed for any of those
+    /// records keeps working. Returns the cadet, which the caller saves.
+    pub fn revoke_card(uuid: &str, tenant: &str) -> Result<User, std::io::Error> {
+        let mut cadet = User::read_for_tenant(uuid.to_string(), tenant)?;
+
+        let duplicates: Vec<User> = read_stored_users()?
+            .into_iter()
+            .filter(|f| f.merged_into.is_some())
+            .collect();
+        let merged_into: std::collections::HashMap<String, String> = duplicates
+            .iter()
+            .filter_map(|f| Some((f.uuid.clone(), f.merged_into.clone()?)))
+            .collect();
+
+        for mut duplicate in duplicates {
+            let mut target = duplicate.merged_into.as_ref();
+            for _ in 0..MAX_REDIRECTS {
+                match target {
+                    Some(t) if *t == cadet.uuid => {
+                        duplicate.card_generation += 1;
+                        duplicate.write_to_disk()?;
+                        break;
+                    }
+                    Some(t) => target = merged_into.get(t),
+                    None => break,
+                }
+            }
+        }
+
+        cadet.card_generation += 1;
+        Ok(cadet)
+    }
+
+    /// What a cadet sees of their own record, leaves out who inspected them, staff notes, voided
+    /// inspections and amendment history
+    #[derive(Serialize, Debug)]
+    pub struct CadetView {
+        uuid: String,
+        username: Option<String>,
+        first_name: Option<String>,
+        last_name: Option<String>,
+        rank: Option<String>,
+        flight: Option<String>,
+        /// Newest first
+        inspections: Vec<CadetViewInspection>,
+        /// Average percentage across every inspection
+        average: Option<f32>,
+        /// Percentage points between the latest inspection and the one before it
+        change: Option<f32>,
+    }
+
+    #[derive(Serialize, Debug)]
+    struct CadetViewInspection {
+        name: String,
+        date: Option<i64>,
+        score: InspectionScore,
+        percentage: Option<f32>,
+        criteria: Vec<Criteria>,
+    }
+
+    impl From<&User> for CadetView {
+        fn from(value: &User) -> Self {
+            let mut inspections: Vec<CadetViewInspection> = value
+                .counted_inspections()
+                .map(|f| {
+                    let score = f.get_score();
+                    CadetViewInspection {
+                        name: f.name.clone(),
+                        date: f.date,
+                        percentage: score.percentage(),
+                        score,
+                        criteria: f.criteria.clone(),
+                    }
+                })
+                .collect();
+            inspections.sort_by_key(|f| std::cmp::Reverse(f.date));
+
+            let percentages: Vec<f32> = inspections.iter().filter_map(|f| f.percentage).collect();
+            let average = match percentages.len() {
+                0 => None,
+                n => Some(percentages.iter().sum::<f32>() / n as f32),
+            };
+            let change = match percentages[..] {
+                [latest, previous, ..] => Some(latest - previous),
+                _ => None,
+            };
+
             Self {
-                user_uuid: value.uuid,
-                name: value.username,
-                flight: value.flight,
-                latest_inspection_date: last_inspection,
-                latest_inspection_score,
+                uuid: value.uuid.clone(),
+                username: value.username.clone(),
+                first_name: value.profile.first_name.clone(),
+                last_name: value.profile.last_name.clone(),
+                rank: value.profile.rank.clone(),
+                flight: value.flight.clone(),
+                inspections,
+                average,
+                change,
             }
         }
     }
 
-    /// Reads all user and stores their uuid and flight
-    pub fn index_users() -> Result<Vec<FlightIndexItem>, std::io::Error> {
-        let files = fs::read_dir("./database/users/")?;
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum EventStatus {
+        Open,
+        Closed,
+    }
+
+    /// A scheduled inspection, such as a parade night
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Event {
+        #[serde(default)]
+        pub id: String,
+        pub name: String,
+        pub date: i64,
+        /// Name of the inspection template used
+        pub template: String,
+        /// Units being inspected, cadets in sections below these units are included
+        pub flights: Vec<String>,
+        #[serde(default = "open")]
+        pub status: EventStatus,
+        #[serde(default)]
+        pub closed_at: Option<i64>,
+        #[serde(default = "default_tenant")]
+        pub tenant: String,
+        #[serde(default)]
+        pub assignments: Vec<Assignment>,
+    }
 
-        let users: Vec<FlightIndexItem> = files
+    /// The cadets an inspector is responsible for during an event
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Assignment {
+        /// Uuid of the authenticated user doing the inspecting
+        pub inspector: String,
+        /// Units assigned as a whole, including their sections
+        #[serde(default)]
+        pub flights: Vec<String>,
+        /// Individual cadets, these take precedence over assigned flights
+        #[serde(default)]
+        pub cadets: Vec<String>,
+    }
+
+    fn open() -> EventStatus {
+        EventStatus::Open
+    }
+
+    fn load_all_events() -> Result<Vec<Event>, std::io::Error> {
+        match fs::read_to_string("./database/events.json") {
+            Ok(t) => Ok(serde_json::from_str(t.as_str())?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// A tenant's events, newest first
+    pub fn load_events(tenant: &str) -> Result<Vec<Event>, std::io::Error> {
+        let mut events: Vec<Event> = load_all_events()?
             .into_iter()
-            .filter_map(|x| x.ok())
-            .filter_map(|x| {
-                serde_json::from_str::<User>(fs::read_to_string(x.path()).ok()?.as_str()).ok()
-            })
-            .map(|x| {
-                let latest_inspection_date = x.get_latest_inspection_date();
-                let latest_inspection_score = x.get_latest_inspection_score();
-                FlightIndexItem {
-                    user_uuid: x.uuid,
-                    flight: x.flight,
-                    name: x.username,
-                    latest_inspection_date,
-                    latest_inspection_score,
+            .filter(|f| f.tenant == tenant)
+            .collect();
+        events.sort_by_key(|f| std::cmp::Reverse(f.date));
+
+        Ok(events)
+    }
+
+    pub fn find_event(id: &str, tenant: &str) -> Result<Event, std::io::Error> {
+        load_events(tenant)?
+            .into_iter()
+            .find(|f| f.id == id)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Event not found"))
+    }
+
+    impl Event {
+        /// Creates the event, or replaces the event with the same id
+        pub fn save(&self) -> Result<(), std::io::Error> {
+            let mut events = load_all_events()?;
+            match events.iter().position(|f| f.id == self.id) {
+                None => events.push(self.clone()),
+                Some(t) => events[t] = self.clone(),
+            };
+
+            fs::write("./database/events.json", serde_json::to_string(&events)?)?;
+            records_changed();
+            Ok(())
+        }
+
+        /// Active cadets in the units being inspected
+        pub fn expected_cadets(&self) -> Result<Vec<User>, std::io::Error> {
+            let units = load_tenant_units(&self.tenant)?;
+
+            Ok(read_tenant_users(&self.tenant)?
+                .into_iter()
+                .filter(|f| f.archived.is_none() && f.profile.status == CadetStatus::Active)
+                .filter(|f| self.includes(f, &units))
+                .collect())
+        }
+
+        /// Whether the cadet is in one of the units being inspected
+        pub fn includes(&self, cadet: &User, units: &[Unit]) -> bool {
+            match &cadet.flight {
+                Some(flight) => self.flights.iter().any(|t| unit_within(units, flight, t)),
+                None => false,
+            }
+        }
+
+        /// Expected cadets that have not been inspected during the event yet
+        pub fn remaining_cadets(&self) -> Result<Vec<User>, std::io::Error> {
+            Ok(self
+                .expected_cadets()?
+                .into_iter()
+                .filter(|f| {
+                    !f.counted_inspections()
+                        .any(|i| i.event.as_ref() == Some(&self.id))
+                })
+                .collect())
+        }
+
+        /// Who is responsible for inspecting a cadet, `None` if nobody has been assigned
+        pub fn assigned_inspector(&self, cadet: &User, units: &[Unit]) -> Option<&str> {
+            let by_cadet = self
+                .assignments
+                .iter()
+                .find(|f| f.cadets.contains(&cadet.uuid));
+            let by_flight = || {
+                self.assignments.iter().find(|f| match &cadet.flight {
+                    Some(flight) => f.flights.iter().any(|t| unit_within(units, flight, t)),
+                    None => false,
+                })
+            };
+
+            by_cadet.or_else(by_flight).map(|f| f.inspector.as_str())
+        }
+
+        /// Remaining cadets assigned to an inspector, ordered by flight then name
+        pub fn queue(&self, inspector: &str) -> Result<Vec<FlightIndexItem>, std::io::Error> {
+            let units = load_tenant_units(&self.tenant)?;
+            let mut cadets: Vec<User> = self
+                .remaining_cadets()?
+                .into_iter()
+                .filter(|f| self.assigned_inspector(f, &units) == Some(inspector))
+                .collect();
+            cadets.sort_by(|a, b| {
+                (&a.flight, &a.profile.last_name, &a.profile.first_name).cmp(&(
+                    &b.flight,
+                    &b.profile.last_name,
+                    &b.profile.first_name,
+                ))
+            });
+
+            Ok(cadets.into_iter().map(FlightIndexItem::from).collect())
+        }
+
+        /// Removes the absent inspectors and hands their remaining cadets, along with any
+        /// unassigned ones, to whichever present inspector has the shortest queue
+        pub fn rebalance(&mut self, absent: &[String]) -> Result<(), std::io::Error> {
+            let units = load_tenant_units(&self.tenant)?;
+            let remaining = self.remaining_cadets()?;
+
+            let mut present: Vec<String> = self
+                .assignments
+                .iter()
+                .map(|f| f.inspector.clone())
+                .filter(|f| !absent.contains(f))
+                .collect();
+            present.sort();
+            present.dedup();
+            if present.is_empty() {
+                return Err(std::io::Error::other("No inspectors left to assign to"));
+            }
+
+            let mut loads = vec![0usize; present.len()];
+            let mut orphans = vec![];
+            for cadet in remaining.iter() {
+                match self
+                    .assigned_inspector(cadet, &units)
+                    .and_then(|t| present.iter().position(|f| f == t))
+                {
+                    Some(t) => loads[t] += 1,
+                    None => orphans.push(cadet.uuid.clone()),
+                }
+            }
+
+            self.assignments.retain(|f| present.contains(&f.inspector));
+            for cadet in orphans {
+                let (index, _) = loads
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, load)| **load)
+                    .expect("There is at least one present inspector");
+                loads[index] += 1;
+
+                let assignment = self
+                    .assignments
+                    .iter_mut()
+                    .find(|f| f.inspector == present[index])
+                    .expect("Present inspectors come from the assignments");
+                assignment.cadets.push(cadet);
+            }
+
+            Ok(())
+        }
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct EventResult {
+        user_uuid: String,
+        name: Option<String>,
+        flight: Option<String>,
+        score: InspectionScore,
+        percentage: Option<f32>,
+    }
+
+    /// Who has and has not been inspected during an event, with the results
+    #[derive(Serialize, Debug)]
+    pub struct EventReport {
+        event: Event,
+        inspected: Vec<EventResult>,
+        not_inspected: Vec<FlightIndexItem>,
+        average: Option<f32>,
+    }
+
+    impl EventReport {
+        pub fn new(event: Event) -> Result<Self, std::io::Error> {
+            let mut inspected = vec![];
+            let mut not_inspected = vec![];
+
+            // Cadets inspected during the event count even if they were not expected, e.g. after
+            // changing flights
+            let mut cadets = event.expected_cadets()?;
+            for user in read_tenant_users(&event.tenant)? {
+                if !cadets.iter().any(|f| f.uuid == user.uuid)
+                    && user
+                        .counted_inspections()
+                        .any(|f| f.event.as_ref() == Some(&event.id))
+                {
+                    cadets.push(user);
+                }
+            }
+
+            for user in cadets {
+                let inspection = user
+                    .counted_inspections()
+                    .filter(|f| f.event.as_ref() == Some(&event.id))
+                    .max_by_key(|f| f.date);
+
+                match inspection {
+                    Some(t) => {
+                        let score = t.get_score();
+                        inspected.push(EventResult {
+                            user_uuid: user.uuid.clone(),
+                            name: user.username.clone(),
+                            flight: user.flight.clone(),
+                            percentage: score.percentage(),
+                            score,
+                        })
+                    }
+                    None => not_inspected.push(FlightIndexItem::from(user)),
                 }
+            }
+
+            let percentages: Vec<f32> = inspected.iter().filter_map(|f| f.percentage).collect();
+            let average = match percentages.len() {
+                0 => None,
+                n => Some(percentages.iter().sum::<f32>() / n as f32),
+            };
+
+            Ok(Self {
+                event,
+                inspected,
+                not_inspected,
+                average,
             })
+        }
+    }
+
+    /// An event's results reduced to totals, for reporting across events
+    #[derive(Serialize, Debug)]
+    pub struct EventSummary {
+        event: Event,
+        inspected: usize,
+        not_inspected: usize,
+        average: Option<f32>,
+    }
+
+    impl From<EventReport> for EventSummary {
+        fn from(report: EventReport) -> Self {
+            Self {
+                inspected: report.inspected.len(),
+                not_inspected: report.not_inspected.len(),
+                average: report.average,
+                event: report.event,
+            }
+        }
+    }
+
+    /// Totals for each of a tenant's events, newest first
+    pub fn summarize_events(tenant: &str) -> Result<Vec<EventSummary>, std::io::Error> {
+        load_events(tenant)?
+            .into_iter()
+            .map(|f| EventReport::new(f).map(EventSummary::from))
+            .collect()
+    }
+
+    /// How flights with the same points are ordered, applied in turn until one differs
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TieBreaker {
+        /// Higher attendance over the period
+        Attendance,
+        /// Larger share of the flight inspected at least once
+        Participation,
+        /// Higher median inspection score
+        Median,
+        /// Higher single best inspection score
+        BestScore,
+    }
+
+    fn default_tie_breakers() -> Vec<TieBreaker> {
+        vec![TieBreaker::Attendance, TieBreaker::Participation]
+    }
+
+    /// A period of the inter-flight competition
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Competition {
+        #[serde(default)]
+        pub id: String,
+        pub name: String,
+        /// Inspections from this timestamp onwards count
+        pub start: i64,
+        /// Inspections before this timestamp count
+        pub end: i64,
+        #[serde(default = "default_tie_breakers")]
+        pub tie_breakers: Vec<TieBreaker>,
+        #[serde(default = "default_tenant")]
+        pub tenant: String,
+        /// Standings saved once the period has finished, so later changes to flights and cadets
+        /// do not rewrite past results
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub results: Option<Vec<super::analytics::Standing>>,
+    }
+
+    fn load_all_competitions() -> Result<Vec<Competition>, std::io::Error> {
+        match fs::read_to_string("./database/competitions.json") {
+            Ok(t) => Ok(serde_json::from_str(t.as_str())?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// A tenant's competition periods, newest first
+    pub fn load_competitions(tenant: &str) -> Result<Vec<Competition>, std::io::Error> {
+        let mut competitions: Vec<Competition> = load_all_competitions()?
+            .into_iter()
+            .filter(|f| f.tenant == tenant)
             .collect();
+        competitions.sort_by_key(|f| std::cmp::Reverse(f.start));
+
+        Ok(competitions)
+    }
+
+    pub fn find_competition(id: &str, tenant: &str) -> Result<Competition, std::io::Error> {
+        load_competitions(tenant)?
+            .into_iter()
+            .find(|f| f.id == id)
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::NotFound, "Competition not found")
+            })
+    }
+
+    impl Competition {
+        /// Creates the competition, or replaces the competition with the same id
+        pub fn save(&self) -> Result<(), std::io::Error> {
+            let mut competitions = load_all_competitions()?;
+            match competitions.iter().position(|f| f.id == self.id) {
+                None => competitions.push(self.clone()),
+                Some(t) => competitions[t] = self.clone(),
+            };
+
+            fs::write(
+                "./database/competitions.json",
+                serde_json::to_string(&competitions)?,
+            )
+        }
+
+        pub fn contains(&self, date: i64) -> bool {
+            date >= self.start && date < self.end
+        }
+
+        /// Whether the saved results of `self` still describe `other`, they do unless the period
+        /// or the way ties are broken changed
+        pub fn same_period(&self, other: &Competition) -> bool {
+            self.start == other.start
+                && self.end == other.end
+                && self.tie_breakers == other.tie_breakers
+        }
+    }
+
+    /// An inspection saved part way through so it can be resumed later, an inspector has at most
+    /// one draft per cadet
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Draft {
+        /// uuid of the authenticated user the draft belongs to
+        pub inspector: String,
+        pub user_uuid: String,
+        pub inspection: Inspection,
+        pub updated_at: i64,
+    }
+
+    impl Draft {
+        /// Both ids must be uuids so a request can never point outside the drafts directory
+        fn path(inspector: &str, user_uuid: &str) -> Result<String, std::io::Error> {
+            if Uuid::parse_str(inspector).is_err() || Uuid::parse_str(user_uuid).is_err() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Invalid draft id",
+                ));
+            }
+
+            Ok(format!("./database/drafts/{inspector}/{user_uuid}.json"))
+        }
+
+        pub fn save(&self) -> Result<(), std::io::Error> {
+            let path = Self::path(&self.inspector, &self.user_uuid)?;
+            fs::create_dir_all(format!("./database/drafts/{}", self.inspector))?;
+            fs::write(path, serde_json::to_string(self)?)
+        }
+
+        pub fn read(inspector: &str, user_uuid: &str) -> Result<Draft, std::io::Error> {
+            Ok(serde_json::from_str(
+                fs::read_to_string(Self::path(inspector, user_uuid)?)?.as_str(),
+            )?)
+        }
+
+        /// All drafts belonging to an inspector, most recently updated first
+        pub fn list(inspector: &str) -> Result<Vec<Draft>, std::io::Error> {
+            let files = match fs::read_dir(format!("./database/drafts/{inspector}")) {
+                Ok(t) => t,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+                Err(e) => return Err(e),
+            };
+
+            let mut drafts: Vec<Draft> = files
+                .filter_map(|x| x.ok())
+                .filter_map(|x| {
+                    serde_json::from_str(fs::read_to_string(x.path()).ok()?.as_str()).ok()
+                })
+                .collect();
+            drafts.sort_by_key(|f: &Draft| std::cmp::Reverse(f.updated_at));
+
+            Ok(drafts)
+        }
+
+        pub fn delete(inspector: &str, user_uuid: &str) -> Result<(), std::io::Error> {
+            fs::remove_file(Self::path(inspector, user_uuid)?)
+        }
+
+        /// Removes every inspector's draft for a cadet
+        pub fn delete_for_user(user_uuid: &str) -> Result<(), std::io::Error> {
+            let inspectors = match fs::read_dir("./database/drafts") {
+                Ok(t) => t,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+                Err(e) => return Err(e),
+            };
+
+            for inspector in inspectors.filter_map(|x| x.ok()) {
+                let draft = inspector.path().join(format!("{user_uuid}.json"));
+                if draft.exists() {
+                    fs::remove_file(draft)?;
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Loads the inspection templates available to a tenant, optionally only those that apply to
+    /// a rank
+    pub fn load_inspection_list(
+        tenant: &str,
+        rank: Option<&str>,
+    ) -> Result<Vec<Inspection>, std::io::Error> {
+        let inspection_lists: Vec<Inspection> =
+            serde_json::from_str(fs::read_to_string("./database/inspections.json")?.as_str())
+                .expect("Invalid Yaml in Inspection List");
+
+        Ok(inspection_lists
+            .into_iter()
+            .filter(|f| f.tenant.as_deref().is_none_or(|t| t == tenant))
+            .filter(|f| match rank {
+                Some(rank) => f.ranks.is_empty() || f.ranks.iter().any(|r| r == rank),
+                None => true,
+            })
+            .collect())
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct DeficiencyCode {
+        /// Short identifier stored on criteria, e.g. `boots-scuffed`
+        pub code: String,
+        /// The criteria category this code is normally used with, e.g. `Boots`
+        pub category_name: Option<String>,
+        pub description: String,
+        #[serde(default = "default_tenant")]
+        pub tenant: String,
+    }
+
+    fn load_all_deficiency_codes() -> Result<Vec<DeficiencyCode>, std::io::Error> {
+        match fs::read_to_string("./database/deficiency_codes.json") {
+            Ok(t) => Ok(serde_json::from_str(t.as_str())?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn load_deficiency_codes(tenant: &str) -> Result<Vec<DeficiencyCode>, std::io::Error> {
+        Ok(load_all_deficiency_codes()?
+            .into_iter()
+            .filter(|f| f.tenant == tenant)
+            .collect())
+    }
+
+    /// Replaces a tenant's deficiency catalogue
+    pub fn write_deficiency_codes(
+        tenant: &str,
+        codes: Vec<DeficiencyCode>,
+    ) -> Result<(), std::io::Error> {
+        let mut all_codes = load_all_deficiency_codes()?;
+        all_codes.retain(|f| f.tenant != tenant);
+        all_codes.extend(codes.into_iter().map(|f| DeficiencyCode {
+            tenant: tenant.into(),
+            ..f
+        }));
 
         fs::write(
-            "./database/flight-index.json",
-            serde_json::to_string(&users)?,
-        )?;
+            "./database/deficiency_codes.json",
+            serde_json::to_string(&all_codes)?,
+        )
+    }
 
-        Ok(users)
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct DeficiencyCount {
+        code: String,
+        category_name: Option<String>,
+        occurrences: usize,
+        cadets: usize,
     }
 
-    pub fn read_user_index() -> Result<Vec<FlightIndexItem>, std::io::Error> {
-        Ok(serde_json::from_str::<Vec<FlightIndexItem>>(
-            fs::read_to_string("./database/flight-index.json")?.as_str(),
-        )?)
+    /// Counts how often each deficiency code has been recorded across a tenant's cadets
+    pub fn count_deficiencies(tenant: &str) -> Result<Vec<DeficiencyCount>, std::io::Error> {
+        let users = read_tenant_users(tenant)?;
+
+        let mut counts: Vec<DeficiencyCount> = load_deficiency_codes(tenant)?
+            .into_iter()
+            .map(|f| DeficiencyCount {
+                code: f.code,
+                category_name: f.category_name,
+                occurrences: 0,
+                cadets: 0,
+            })
+            .collect();
+
+        for count in counts.iter_mut() {
+            let per_user = users.iter().map(|u| {
+                u.counted_inspections()
+                    .flat_map(|i| i.criteria.iter())
+                    .filter(|c| c.deficiency_codes().contains(&count.code))
+                    .count()
+            });
+
+            for occurrences in per_user {
+                count.occurrences += occurrences;
+                count.cadets += (occurrences > 0) as usize;
+            }
+        }
+
+        counts.sort_by_key(|f| std::cmp::Reverse(f.occurrences));
+
+        Ok(counts)
     }
 
-    // pub fn add_user_to_index(u: &User) -> Result<(), std::io::Error> {
-    //     let mut users = read_user_index()?;
-    //     users.push(u.into());
-    //
-    //     fs::write(
-    //         "./database/flight-index.json",
-    //         serde_json::ser::to_string(&users)?,
-    //     )
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct FlightIndexItem {
+        user_uuid: String,
+        flight: Option<String>,
+        name: Option<String>,
+        latest_inspection_date: Option<i64>,
+        latest_inspection_score: Option<InspectionScore>,
+        #[serde(default = "default_tenant")]
+        tenant: String,
+        #[serde(default)]
+        first_name: Option<String>,
+        #[serde(default)]
+        last_name: Option<String>,
+        #[serde(default)]
+        rank: Option<String>,
+        #[serde(default)]
+        status: CadetStatus,
+        #[serde(default)]
+        archived: bool,
+        /// The username a cadet asked for without a claim code, until staff approve it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pending_claim: Option<String>,
+        #[serde(default)]
+        attendance_percentage: Option<f32>,
+    }
+
+    #[derive(Deserialize, Debug, Clone, Copy, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub enum IndexSort {
+        /// By last name, then first name, then username. When searching by name the best matches
+        /// come first instead
+        #[default]
+        Name,
+        Flight,
+        LatestInspectionDate,
+        Score,
+    }
+
+    /// Narrows down, orders and pages the cadet index, every filter left empty matches all cadets
+    #[derive(Deserialize, Debug, Default)]
+    pub struct IndexFilter {
+        /// Fuzzy matched against the username, first and last name
+        pub name: Option<String>,
+        pub flight: Option<String>,
+        /// When not given archived and inactive cadets are hidden unless `include_inactive` is set
+        pub status: Option<CadetStatus>,
+        pub rank: Option<String>,
+        /// Include archived and inactive cadets
+        #[serde(default)]
+        pub include_inactive: bool,
+        pub inspected_after: Option<i64>,
+        pub inspected_before: Option<i64>,
+        /// Percentage of the latest inspection
+        pub min_score: Option<f32>,
+        pub max_score: Option<f32>,
+        #[serde(default)]
+        pub sort: IndexSort,
+        #[serde(default)]
+        pub descending: bool,
+        /// Returned as `next_cursor` by the previous page
+        pub cursor: Option<String>,
+        /// Page size, all matching cadets are returned when not given
+        pub limit: Option<usize>,
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct IndexPage {
+        pub items: Vec<FlightIndexItem>,
+        /// `None` on the last page
+        pub next_cursor: Option<String>,
+    }
+
+    /// Scores how well `query` matches `text`, higher is better. Every character of the query
+    /// must appear in order, consecutive characters and an early first match score higher.
+    fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+        let text: Vec<char> = text.to_lowercase().chars().collect();
+        let mut score = 0;
+        let mut position = 0;
+        let mut previous: Option<usize> = None;
+
+        for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+            let found = position + text[position..].iter().position(|f| *f == c)?;
+            score += match previous {
+                Some(p) if p + 1 == found => 3,
+                _ => 1,
+            };
+            if previous.is_none() {
+                score -= found.min(10) as i32;
+            }
+            previous = Some(found);
+            position = found + 1;
+        }
+
+        Some(score)
+    }
+
+    impl IndexFilter {
+        fn name_score(&self, item: &FlightIndexItem) -> Option<i32> {
+            let query = self.name.as_deref()?;
+
+            [&item.name, &item.first_name, &item.last_name]
+                .into_iter()
+                .flatten()
+                .filter_map(|f| fuzzy_score(query, f))
+                .max()
+        }
+
+        pub fn matches(&self, item: &FlightIndexItem) -> bool {
+            let status = match self.status {
+                Some(status) => item.status == status,
+                None => {
+                    self.include_inactive || (!item.archived && item.status == CadetStatus::Active)
+                }
+            };
+            let score = item
+                .latest_inspection_score
+                .as_ref()
+                .and_then(InspectionScore::percentage);
+            let date = item.latest_inspection_date;
+
+            status
+                && (self.name.is_none() || self.name_score(item).is_some())
+                && (self.flight.is_none() || self.flight == item.flight)
+                && (self.rank.is_none() || self.rank == item.rank)
+                && self
+                    .inspected_after
+                    .is_none_or(|t| date.is_some_and(|d| d >= t))
+                && self
+                    .inspected_before
+                    .is_none_or(|t| date.is_some_and(|d| d <= t))
+                && self.min_score.is_none_or(|t| score.is_some_and(|s| s >= t))
+                && self.max_score.is_none_or(|t| score.is_some_and(|s| s <= t))
+        }
+
+        /// Where an item falls in the requested order
+        fn sort_key(&self, item: &FlightIndexItem) -> SortKey {
+            let name = [&item.last_name, &item.first_name, &item.name]
+                .map(|f| f.as_ref().map(|f| f.to_lowercase()));
+
+            let (number, text) = match (self.sort, self.name.is_some()) {
+                // Best matches first
+                (IndexSort::Name, true) => {
+                    (self.name_score(item).map(|f| -f as f64), name.to_vec())
+                }
+                (IndexSort::Name, false) => (None, name.to_vec()),
+                (IndexSort::Flight, _) => (
+                    None,
+                    std::iter::once(item.flight.clone()).chain(name).collect(),
+                ),
+                (IndexSort::LatestInspectionDate, _) => {
+                    (item.latest_inspection_date.map(|f| f as f64), name.to_vec())
+                }
+                (IndexSort::Score, _) => (
+                    item.latest_inspection_score
+                        .as_ref()
+                        .and_then(InspectionScore::percentage)
+                        .map(f64::from),
+                    name.to_vec(),
+                ),
+            };
+
+            SortKey {
+                number,
+                text,
+                uuid: item.user_uuid.clone(),
+            }
+        }
+
+        /// Filters and sorts the index, then cuts out the page following the cursor
+        pub fn apply(&self, index: Vec<FlightIndexItem>) -> Result<IndexPage, std::io::Error> {
+            let mut items: Vec<(SortKey, FlightIndexItem)> = index
+                .into_iter()
+                .filter(|f| self.matches(f))
+                .map(|f| (self.sort_key(&f), f))
+                .collect();
+
+            let order = |a: &SortKey, b: &SortKey| {
+                let order = a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal);
+                match self.descending {
+                    true => order.reverse(),
+                    false => order,
+                }
+            };
+            items.sort_by(|a, b| order(&a.0, &b.0));
+
+            // The cursor is the key of the last item on the previous page, so cadets added or
+            // removed between requests do not shift the pages
+            let start = match &self.cursor {
+                Some(t) => {
+                    let after = SortKey::decode(t).ok_or_else(|| {
+                        std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid cursor")
+                    })?;
+                    items.partition_point(|f| order(&f.0, &after) != std::cmp::Ordering::Greater)
+                }
+                None => 0,
+            };
+            let end = match self.limit {
+                Some(limit) => start.saturating_add(limit).min(items.len()),
+                None => items.len(),
+            };
+
+            Ok(IndexPage {
+                next_cursor: match end < items.len() && end > start {
+                    true => Some(items[end - 1].0.encode()?),
+                    false => None,
+                },
+                items: items.drain(start..end).map(|f| f.1).collect(),
+            })
+        }
+    }
+
+    /// An item's position in the index, the uuid breaks ties so no two items share a key
+    #[derive(Serialize, Deserialize, Debug, PartialEq, PartialOrd)]
+    struct SortKey {
+        number: Option<f64>,
+        text: Vec<Option<String>>,
+        uuid: String,
+    }
+
+    impl SortKey {
+        /// Kept opaque to callers so the cursor can change without breaking them
+        fn encode(&self) -> Result<String, std::io::Error> {
+            use base64::Engine;
+
+            Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(self)?))
+        }
+
+        fn decode(cursor: &str) -> Option<Self> {
+            use base64::Engine;
+
+            serde_json::from_slice(
+                &base64::engine::general_purpose::URL_SAFE_NO_PAD
+                    .decode(cursor)
+                    .ok()?,
+            )
+            .ok()
+        }
+    }
+
+    impl From<&User> for FlightIndexItem {
+        fn from(value: &User) -> Self {
+            value.clone().into()
+        }
+    }
+
+    impl From<User> for FlightIndexItem {
+        fn from(value: User) -> Self {
+            let last_inspection = value.get_latest_inspection_date();
+            let latest_inspection_score = value.get_latest_inspection_score();
+            let attendance_percentage = value.attendance_percentage();
+            Self {
+                user_uuid: value.uuid,
+                name: value.username,
+                flight: value.flight,
+                latest_inspection_date: last_inspection,
+                latest_inspection_score,
+                tenant: value.tenant,
+                first_name: value.profile.first_name,
+                last_name: value.profile.last_name,
+                rank: value.profile.rank,
+                status: value.profile.status,
+                archived: value.archived.is_some(),
+                pending_claim: value.pending_claim,
+                attendance_percentage,
+            }
+        }
+    }
+
+    /// Reads every cadet in the database, files that fail to parse and merged duplicates are
+    /// skipped
+    pub fn read_all_users() -> Result<Vec<User>, std::io::Error> {
+        Ok(read_stored_users()?
+            .into_iter()
+            .filter(|x| x.merged_into.is_none())
+            .collect())
+    }
+
+    /// Every record on disk including merged duplicates, files that fail to parse are skipped
+    fn read_stored_users() -> Result<Vec<User>, std::io::Error> {
+        let files = fs::read_dir("./database/users/")?;
+
+        Ok(files
+            .into_iter()
+            .filter_map(|x| x.ok())
+            .filter_map(|x| {
+                serde_json::from_str::<User>(fs::read_to_string(x.path()).ok()?.as_str()).ok()
+            })
+            .collect())
+    }
+
+    pub fn read_tenant_users(tenant: &str) -> Result<Vec<User>, std::io::Error> {
+        Ok(read_all_users()?
+            .into_iter()
+            .filter(|f| f.tenant == tenant)
+            .collect())
+    }
+
+    /// Reads all user and stores their uuid and flight
+    pub fn index_users() -> Result<Vec<FlightIndexItem>, std::io::Error> {
+        let users: Vec<FlightIndexItem> = read_all_users()?
+            .into_iter()
+            .map(FlightIndexItem::from)
+            .collect();
+
+        fs::write(
+            "./database/flight-index.json",
+            serde_json::to_string(&users)?,
+        )?;
+        *USER_INDEX.write().expect("User index lock poisoned") = Some(users.clone());
+        records_changed();
+
+        Ok(users)
+    }
+
+    /// The most recent result of [`index_users`], kept in memory so the index does not have to be
+    /// read from disk on every request
+    static USER_INDEX: std::sync::RwLock<Option<Vec<FlightIndexItem>>> =
+        std::sync::RwLock::new(None);
+
+    static INDEX_GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+    /// Changes every time cadets are re-indexed or units or events are written, so anything
+    /// derived from those records can tell when it is out of date
+    pub fn index_generation() -> u64 {
+        INDEX_GENERATION.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn records_changed() {
+        INDEX_GENERATION.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn read_user_index(tenant: &str) -> Result<Vec<FlightIndexItem>, std::io::Error> {
+        let cached = USER_INDEX.read().expect("User index lock poisoned").clone();
+
+        let index = match cached {
+            Some(t) => t,
+            None => serde_json::from_str::<Vec<FlightIndexItem>>(
+                fs::read_to_string("./database/flight-index.json")?.as_str(),
+            )?,
+        };
+
+        Ok(index.into_iter().filter(|f| f.tenant == tenant).collect())
+    }
+
+    // pub fn add_user_to_index(u: &User) -> Result<(), std::io::Error> {
+    //     let mut users = read_user_index()?;
+    //     users.push(u.into());
+    //
+    //     fs::write(
+    //         "./database/flight-index.json",
+    //         serde_json::ser::to_string(&users)?,
+    //     )
     // }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn promotions(user: &User) -> Vec<(i64, &str, Option<&str>)> {
+            user.promotions
+                .iter()
+                .map(|f| (f.date, f.rank.as_str(), f.previous_rank.as_deref()))
+                .collect()
+        }
+
+        #[test]
+        fn promotions_in_order_chain_ranks() {
+            let mut user = User::new(default_tenant());
+            user.profile.rank = Some("AC".into());
+            user.promote("LAC".into(), 100, "staff".into());
+            user.promote("Cpl".into(), 200, "staff".into());
+
+            assert_eq!(
+                promotions(&user),
+                vec![(100, "LAC", Some("AC")), (200, "Cpl", Some("LAC"))]
+            );
+            assert_eq!(user.profile.rank.as_deref(), Some("Cpl"));
+        }
+
+        #[test]
+        fn backdated_promotion_is_inserted_in_date_order() {
+            let mut user = User::new(default_tenant());
+            user.profile.rank = Some("AC".into());
+            user.promote("FCpl".into(), 300, "staff".into());
+            user.promote("LAC".into(), 100, "staff".into());
+            user.promote("Sgt".into(), 400, "staff".into());
+            user.promote("Cpl".into(), 200, "staff".into());
+
+            assert_eq!(
+                promotions(&user),
+                vec![
+                    (100, "LAC", Some("AC")),
+                    (200, "Cpl", Some("LAC")),
+                    (300, "FCpl", Some("Cpl")),
+                    (400, "Sgt", Some("FCpl")),
+                ]
+            );
+            // Only the latest promotion sets the current rank
+            assert_eq!(user.profile.rank.as_deref(), Some("Sgt"));
+        }
+
+        #[test]
+        fn same_day_promotions_keep_recorded_order() {
+            let mut user = User::new(default_tenant());
+            user.promote("LAC".into(), 100, "staff".into());
+            user.promote("Cpl".into(), 100, "staff".into());
+            user.promote("AC".into(), 50, "staff".into());
+
+            assert_eq!(
+                promotions(&user),
+                vec![
+                    (50, "AC", None),
+                    (100, "LAC", Some("AC")),
+                    (100, "Cpl", Some("LAC")),
+                ]
+            );
+            assert_eq!(user.profile.rank.as_deref(), Some("Cpl"));
+        }
+
+        fn item(uuid: &str, date: Option<i64>) -> FlightIndexItem {
+            FlightIndexItem {
+                user_uuid: uuid.into(),
+                flight: None,
+                name: None,
+                latest_inspection_date: date,
+                latest_inspection_score: None,
+                tenant: default_tenant(),
+                first_name: None,
+                last_name: None,
+                rank: None,
+                status: CadetStatus::Active,
+                archived: false,
+                pending_claim: None,
+                attendance_percentage: None,
+            }
+        }
+
+        /// `b`, `c` and `d` tie on date so only their uuids order them
+        fn index() -> Vec<FlightIndexItem> {
+            vec![
+                item("d", Some(2)),
+                item("a", Some(1)),
+                item("e", Some(3)),
+                item("b", Some(2)),
+                item("f", None),
+                item("c", Some(2)),
+            ]
+        }
+
+        /// Follows `next_cursor` until the last page, returning the uuids of each page
+        fn pages(filter: &mut IndexFilter, index: &[FlightIndexItem]) -> Vec<Vec<String>> {
+            let mut pages = vec![];
+            loop {
+                let page = filter.apply(index.to_vec()).unwrap();
+                pages.push(page.items.into_iter().map(|f| f.user_uuid).collect());
+                match page.next_cursor {
+                    Some(t) => filter.cursor = Some(t),
+                    None => return pages,
+                }
+            }
+        }
+
+        #[test]
+        fn cursor_pages_through_ties_once_each() {
+            let mut filter = IndexFilter {
+                sort: IndexSort::LatestInspectionDate,
+                limit: Some(2),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                pages(&mut filter, &index()),
+                vec![vec!["f", "a"], vec!["b", "c"], vec!["d", "e"]]
+            );
+        }
+
+        #[test]
+        fn cursor_pages_descending() {
+            let mut filter = IndexFilter {
+                sort: IndexSort::LatestInspectionDate,
+                descending: true,
+                limit: Some(4),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                pages(&mut filter, &index()),
+                vec![vec!["e", "d", "c", "b"], vec!["a", "f"]]
+            );
+        }
+
+        #[test]
+        fn cursor_is_not_shifted_by_new_cadets() {
+            let mut filter = IndexFilter {
+                sort: IndexSort::LatestInspectionDate,
+                limit: Some(2),
+                ..Default::default()
+            };
+            let first = filter.apply(index()).unwrap();
+            filter.cursor = first.next_cursor;
+
+            // Sorts before the cursor, so it belongs to a page that was already read
+            let mut index = index();
+            index.push(item("0", Some(0)));
+            let second = filter.apply(index).unwrap();
+
+            assert_eq!(
+                second
+                    .items
+                    .iter()
+                    .map(|f| f.user_uuid.as_str())
+                    .collect::<Vec<_>>(),
+                vec!["b", "c"]
+            );
+        }
+
+        #[test]
+        fn invalid_cursor_is_rejected() {
+            let filter = IndexFilter {
+                cursor: Some("not a cursor".into()),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                filter.apply(index()).err().map(|f| f.kind()),
+                Some(std::io::ErrorKind::InvalidInput)
+            );
+        }
+
+        const WINDOW_END: i64 = 1_000_000;
+
+        fn cadet() -> User {
+            CARD_KEY.get_or_init(|| b"test key".to_vec());
+            User::new(default_tenant())
+        }
+
+        fn check(code: &str, stored: &User, now: i64) -> Result<(), CardError> {
+            check_card(&parse_card(code)?, stored, Some(WINDOW_END), now)
+        }
+
+        #[test]
+        fn signed_code_round_trips() {
+            let user = cadet();
+            let code = card_code(&user);
+
+            assert!(card_is_signed(&code));
+            assert_eq!(
+                parse_card(&code).map(|f| f.uuid().to_owned()),
+                Ok(user.uuid.clone())
+            );
+            assert_eq!(check(&code, &user, WINDOW_END + 1), Ok(()));
+        }
+
+        #[test]
+        fn tampered_signature_is_invalid() {
+            let user = cadet();
+            let mut code = card_code(&user);
+            let last = match code.pop() {
+                Some('0') => '1',
+                _ => '0',
+            };
+            code.push(last);
+
+            assert_eq!(parse_card(&code), Err(CardError::Invalid));
+
+            // Signed for another cadet
+            let other = cadet();
+            let forged = card_code(&other).replace(&other.uuid, &user.uuid);
+            assert_eq!(parse_card(&forged), Err(CardError::Invalid));
+        }
+
+        #[test]
+        fn revoked_generation_is_rejected() {
+            let mut user = cadet();
+            let old = card_code(&user);
+            user.card_generation += 1;
+
+            assert_eq!(check(&old, &user, 0), Err(CardError::Revoked));
+            assert_eq!(check(&card_code(&user), &user, 0), Ok(()));
+        }
+
+        #[test]
+        fn bare_uuid_only_works_in_the_window() {
+            let mut user = cadet();
+
+            assert!(!card_is_signed(&user.uuid));
+            assert_eq!(check(&user.uuid, &user, WINDOW_END - 1), Ok(()));
+            assert_eq!(
+                check(&user.uuid, &user, WINDOW_END),
+                Err(CardError::Expired)
+            );
+            assert_eq!(parse_card("not-a-uuid"), Err(CardError::Invalid));
+
+            // Revoking a card also revokes the bare uuid printed on it
+            user.card_generation += 1;
+            assert_eq!(check(&user.uuid, &user, 0), Err(CardError::Revoked));
+        }
+
+        #[test]
+        fn merged_record_uses_its_own_generation() {
+            let survivor = cadet();
+            let mut duplicate = cadet();
+            duplicate.merged_into = Some(survivor.uuid.clone());
+            let code = card_code(&duplicate);
+
+            assert_eq!(check(&code, &duplicate, 0), Ok(()));
+
+            duplicate.card_generation += 1;
+            assert_eq!(check(&code, &duplicate, 0), Err(CardError::Revoked));
+            assert_eq!(check(&card_code(&survivor), &survivor, 0), Ok(()));
+        }
+    }
+}
+
+/// Stores photos attached to inspections on local disk under `database/attachments/{user_uuid}/`
+pub mod attachments {
+    use std::fs;
+    use std::io::{Cursor, Error, ErrorKind};
+
+    use image::{imageops::FilterType, ImageFormat};
+    use uuid::Uuid;
+
+    /// Largest upload accepted, in bytes
+    pub const MAX_SIZE: usize = 10 * 1024 * 1024;
+    const THUMBNAIL_SIZE: u32 = 256;
+
+    fn format_for(content_type: &str) -> Option<(ImageFormat, &'static str)> {
+        match content_type {
+            "image/jpeg" => Some((ImageFormat::Jpeg, "jpg")),
+            "image/png" => Some((ImageFormat::Png, "png")),
+            _ => None,
+        }
+    }
+
+    pub fn is_supported(content_type: &str) -> bool {
+        format_for(content_type).is_some()
+    }
+
+    fn path(user_uuid: &str, attachment_id: &str, extension: &str) -> String {
+        format!("./database/attachments/{user_uuid}/{attachment_id}.{extension}")
+    }
+
+    /// Decodes and re-encodes the image, this drops all metadata including EXIF location data,
+    /// then writes it alongside a JPEG thumbnail. Returns the new attachment id.
+    pub fn store(user_uuid: &str, content_type: &str, bytes: &[u8]) -> Result<String, Error> {
+        let (format, extension) = format_for(content_type)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Unsupported image type"))?;
+
+        let image = image::load_from_memory_with_format(bytes, format)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        let id = Uuid::new_v4().to_string();
+        fs::create_dir_all(format!("./database/attachments/{user_uuid}"))?;
+
+        let mut cleaned = Cursor::new(vec![]);
+        image.write_to(&mut cleaned, format).map_err(Error::other)?;
+        fs::write(path(user_uuid, &id, extension), cleaned.into_inner())?;
+
+        let mut thumbnail = Cursor::new(vec![]);
+        image
+            .resize(THUMBNAIL_SIZE, THUMBNAIL_SIZE, FilterType::Triangle)
+            .to_rgb8()
+            .write_to(&mut thumbnail, ImageFormat::Jpeg)
+            .map_err(Error::other)?;
+        fs::write(path(user_uuid, &id, "thumb.jpg"), thumbnail.into_inner())?;
+
+        Ok(id)
+    }
+
+    /// Decodes an uploaded image and re-encodes it as a PNG, scaled down to fit within `max_side`
+    pub fn to_png(content_type: &str, bytes: &[u8], max_side: u32) -> Result<Vec<u8>, Error> {
+        let (format, _) = format_for(content_type)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Unsupported image type"))?;
+
+        let mut image = image::load_from_memory_with_format(bytes, format)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        if image.width() > max_side || image.height() > max_side {
+            image = image.resize(max_side, max_side, FilterType::Triangle);
+        }
+
+        let mut png = Cursor::new(vec![]);
+        image
+            .write_to(&mut png, ImageFormat::Png)
+            .map_err(Error::other)?;
+        Ok(png.into_inner())
+    }
+
+    /// Moves every attachment stored for one cadet to another, used when merging duplicates
+    pub fn move_all(from_uuid: &str, to_uuid: &str) -> Result<(), Error> {
+        let files = match fs::read_dir(format!("./database/attachments/{from_uuid}")) {
+            Ok(t) => t,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        fs::create_dir_all(format!("./database/attachments/{to_uuid}"))?;
+        for file in files.filter_map(|x| x.ok()) {
+            fs::rename(
+                file.path(),
+                format!(
+                    "./database/attachments/{to_uuid}/{}",
+                    file.file_name().to_string_lossy()
+                ),
+            )?;
+        }
+
+        delete_for_user(from_uuid)
+    }
+
+    /// Removes every attachment stored for a cadet
+    pub fn delete_for_user(user_uuid: &str) -> Result<(), Error> {
+        match fs::remove_dir_all(format!("./database/attachments/{user_uuid}")) {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            t => t,
+        }
+    }
+
+    /// Reads a stored attachment, thumbnails are always JPEG
+    pub fn read(
+        user_uuid: &str,
+        attachment_id: &str,
+        content_type: &str,
+        thumbnail: bool,
+    ) -> Result<Vec<u8>, Error> {
+        let (_, extension) = format_for(content_type)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Unsupported image type"))?;
+
+        match thumbnail {
+            true => fs::read(path(user_uuid, attachment_id, "thumb.jpg")),
+            false => fs::read(path(user_uuid, attachment_id, extension)),
+        }
+    }
+}
+
+/// Statistics computed from recorded inspections
+pub mod analytics {
+    use std::sync::Mutex;
+
+    use chrono::{Datelike, NaiveDate};
+    use serde::{Deserialize, Serialize};
+
+    use super::data::{
+        self, Attendance, AttendanceStatus, CadetStatus, Competition, Event, Inspection,
+        TieBreaker, UnitKind, User,
+    };
+
+    /// Change in percentage points between windows below which a cadet is considered steady
+    const STEADY_THRESHOLD: f32 = 5.0;
+
+    /// Largest moving average window a caller may ask for
+    pub const MAX_WINDOW: usize = 100;
+
+    #[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Trend {
+        Improving,
+        Steady,
+        Declining,
+    }
+
+    impl Trend {
+        fn from_change(change: f32) -> Self {
+            match change {
+                t if t > STEADY_THRESHOLD => Trend::Improving,
+                t if t < -STEADY_THRESHOLD => Trend::Declining,
+                _ => Trend::Steady,
+            }
+        }
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct ScorePoint {
+        inspection_id: Option<String>,
+        name: String,
+        date: Option<i64>,
+        percentage: Option<f32>,
+        /// Average of this and the preceding scored inspections within the window
+        moving_average: Option<f32>,
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct CriterionFrequency {
+        category_name: String,
+        /// Times the criterion was assessed, exemptions are not counted
+        assessed: usize,
+        failures: usize,
+        failure_rate: f32,
+        /// Compares the failure rate over the most recent window with the rate before it, a
+        /// falling failure rate is an improvement
+        trend: Option<Trend>,
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct CadetTrends {
+        user_uuid: String,
+        window: usize,
+        /// Oldest first
+        series: Vec<ScorePoint>,
+        /// Most failed first
+        criteria: Vec<CriterionFrequency>,
+        /// Difference between the average of the latest window and the window before it, in
+        /// percentage points
+        change: Option<f32>,
+        trend: Option<Trend>,
+    }
+
+    fn mean(values: &[f32]) -> Option<f32> {
+        match values.len() {
+            0 => None,
+            n => Some(values.iter().sum::<f32>() / n as f32),
+        }
+    }
+
+    /// Average of the last `window` values less the average of the `window` values before them
+    fn window_change(values: &[f32], window: usize) -> Option<f32> {
+        let both = window.checked_mul(2)?;
+        if window == 0 || values.len() < both {
+            return None;
+        }
+
+        let recent = &values[values.len() - window..];
+        let previous = &values[values.len() - both..values.len() - window];
+
+        Some(mean(recent)? - mean(previous)?)
+    }
+
+    /// Counted inspections oldest first, regardless of the order they were read in
+    fn chronological(user: &User) -> Vec<&Inspection> {
+        let mut inspections: Vec<&Inspection> = user.counted_inspections().collect();
+        inspections.sort_by_key(|f| f.date.unwrap_or(0));
+
+        inspections
+    }
+
+    impl CadetTrends {
+        pub fn new(user: &User, window: usize) -> Self {
+            let window = window.max(1);
+
+            let mut scored = vec![];
+            let series = chronological(user)
+                .into_iter()
+                .map(|f| {
+                    let percentage = f.get_score().percentage();
+                    if let Some(t) = percentage {
+                        scored.push(t);
+                    }
+
+                    ScorePoint {
+                        inspection_id: f.id.clone(),
+                        name: f.name.clone(),
+                        date: f.date,
+                        percentage,
+                        moving_average: match percentage {
+                            Some(_) => mean(&scored[scored.len().saturating_sub(window)..]),
+                            None => None,
+                        },
+                    }
+                })
+                .collect();
+
+            let change = window_change(&scored, window);
+
+            Self {
+                user_uuid: user.uuid.clone(),
+                window,
+                series,
+                criteria: criterion_frequencies(user, window),
+                change,
+                trend: change.map(Trend::from_change),
+            }
+        }
+    }
+
+    fn criterion_frequencies(user: &User, window: usize) -> Vec<CriterionFrequency> {
+        // Results per criterion in the order they were inspected, 100 for a pass and 0 for a
+        // failure so the same window comparison as the scores can be used
+        let mut results: Vec<(String, Vec<f32>)> = vec![];
+        for criteria in chronological(user)
+            .into_iter()
+            .flat_map(|f| f.criteria.iter())
+        {
+            let (Some(name), Some(failed)) = (criteria.category_name(), criteria.failed()) else {
+                continue;
+            };
+            let result = if failed { 0.0 } else { 100.0 };
+
+            match results.iter_mut().find(|(f, _)| f == name) {
+                Some((_, t)) => t.push(result),
+                None => results.push((name.to_string(), vec![result])),
+            }
+        }
+
+        let mut frequencies: Vec<CriterionFrequency> = results
+            .into_iter()
+            .map(|(category_name, results)| {
+                let failures = results.iter().filter(|f| **f == 0.0).count();
+                CriterionFrequency {
+                    category_name,
+                    assessed: results.len(),
+                    failures,
+                    failure_rate: failures as f32 / results.len() as f32 * 100.0,
+                    trend: window_change(&results, window).map(Trend::from_change),
+                }
+            })
+            .collect();
+        frequencies.sort_by_key(|f| std::cmp::Reverse(f.failures));
+
+        frequencies
+    }
+
+    #[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Bucket {
+        /// Weeks start on Monday, UTC
+        Week,
+        #[default]
+        Month,
+    }
+
+    impl Bucket {
+        /// Timestamp of the start of the bucket containing `date`
+        fn start(&self, date: i64) -> i64 {
+            let day = chrono::DateTime::from_timestamp(date, 0)
+                .unwrap_or_default()
+                .date_naive();
+            let start = match self {
+                Bucket::Week => {
+                    day - chrono::Duration::days(day.weekday().num_days_from_monday().into())
+                }
+                Bucket::Month => NaiveDate::from_ymd_opt(day.year(), day.month(), 1)
+                    .expect("The first of a month always exists"),
+            };
+
+            start
+                .and_hms_opt(0, 0, 0)
+                .expect("Midnight always exists")
+                .and_utc()
+                .timestamp()
+        }
+    }
+
+    fn pass_mark() -> f32 {
+        70.0
+    }
+
+    fn stale_weeks() -> i64 {
+        4
+    }
+
+    #[derive(Deserialize, Debug, Clone, PartialEq)]
+    pub struct DashboardQuery {
+        #[serde(default)]
+        pub bucket: Bucket,
+        /// Only inspections on or after this timestamp
+        pub since: Option<i64>,
+        /// Only inspections before this timestamp
+        pub until: Option<i64>,
+        /// Percentage an inspection needs to count as a pass
+        #[serde(default = "pass_mark")]
+        pub pass_mark: f32,
+        /// Cadets whose last inspection is older than this many weeks are listed as overdue
+        #[serde(default = "stale_weeks")]
+        pub stale_weeks: i64,
+    }
+
+    #[derive(Serialize, Debug, Clone)]
+    pub struct BucketStats {
+        start: i64,
+        inspections: usize,
+        average: Option<f32>,
+        median: Option<f32>,
+        /// Percentage of scored inspections at or above the pass mark
+        pass_rate: Option<f32>,
+    }
+
+    #[derive(Serialize, Debug, Clone)]
+    pub struct CriterionCount {
+        category_name: String,
+        assessed: usize,
+        failures: usize,
+    }
+
+    #[derive(Serialize, Debug, Clone)]
+    pub struct GroupStats {
+        /// `None` for the whole squadron, or for cadets without a flight
+        flight: Option<String>,
+        cadets: usize,
+        /// Oldest first
+        buckets: Vec<BucketStats>,
+        /// The five criteria failed most often
+        most_failed: Vec<CriterionCount>,
+    }
+
+    #[derive(Serialize, Debug, Clone)]
+    pub struct OverdueCadet {
+        user_uuid: String,
+        name: Option<String>,
+        first_name: Option<String>,
+        last_name: Option<String>,
+        flight: Option<String>,
+        last_inspected: Option<i64>,
+    }
+
+    #[derive(Serialize, Debug, Clone)]
+    pub struct Dashboard {
+        generated_at: i64,
+        squadron: GroupStats,
+        flights: Vec<GroupStats>,
+        /// Active cadets not inspected within `stale_weeks`, never inspected cadets first
+        overdue: Vec<OverdueCadet>,
+    }
+
+    fn median(values: &mut [f32]) -> Option<f32> {
+        values.sort_by(f32::total_cmp);
+        match values.len() {
+            0 => None,
+            n if n % 2 == 0 => Some((values[n / 2 - 1] + values[n / 2]) / 2.0),
+            n => Some(values[n / 2]),
+        }
+    }
+
+    impl GroupStats {
+        fn new(flight: Option<String>, cadets: &[&User], query: &DashboardQuery) -> Self {
+            let inspections: Vec<&Inspection> = cadets
+                .iter()
+                .flat_map(|f| f.counted_inspections())
+                .filter(|f| match f.date {
+                    Some(date) => {
+                        query.since.is_none_or(|t| date >= t)
+                            && query.until.is_none_or(|t| date < t)
+                    }
+                    None => false,
+                })
+                .collect();
+
+            let mut buckets: Vec<(i64, Vec<f32>, usize)> = vec![];
+            for inspection in inspections.iter() {
+                let start = query.bucket.start(inspection.date.unwrap_or_default());
+                let index = match buckets.iter().position(|(f, _, _)| *f == start) {
+                    Some(t) => t,
+                    None => {
+                        buckets.push((start, vec![], 0));
+                        buckets.len() - 1
+                    }
+                };
+
+                buckets[index].2 += 1;
+                if let Some(t) = inspection.get_score().percentage() {
+                    buckets[index].1.push(t);
+                }
+            }
+            buckets.sort_by_key(|(f, _, _)| *f);
+
+            let mut most_failed: Vec<CriterionCount> = vec![];
+            for criteria in inspections.iter().flat_map(|f| f.criteria.iter()) {
+                let (Some(name), Some(failed)) = (criteria.category_name(), criteria.failed())
+                else {
+                    continue;
+                };
+
+                let count = match most_failed.iter().position(|f| f.category_name == name) {
+                    Some(t) => &mut most_failed[t],
+                    None => {
+                        most_failed.push(CriterionCount {
+                            category_name: name.to_string(),
+                            assessed: 0,
+                            failures: 0,
+                        });
+                        most_failed.last_mut().expect("Just pushed")
+                    }
+                };
+                count.assessed += 1;
+                count.failures += failed as usize;
+            }
+            most_failed.retain(|f| f.failures > 0);
+            most_failed.sort_by_key(|f| std::cmp::Reverse(f.failures));
+            most_failed.truncate(5);
+
+            Self {
+                flight,
+                cadets: cadets.len(),
+                buckets: buckets
+                    .into_iter()
+                    .map(|(start, mut scores, inspections)| BucketStats {
+                        start,
+                        inspections,
+                        average: mean(&scores),
+                        pass_rate: match scores.len() {
+                            0 => None,
+                            n => Some(
+                                scores.iter().filter(|f| **f >= query.pass_mark).count() as f32
+                                    / n as f32
+                                    * 100.0,
+                            ),
+                        },
+                        median: median(&mut scores),
+                    })
+                    .collect(),
+                most_failed,
+            }
+        }
+    }
+
+    impl Dashboard {
+        fn new(tenant: &str, query: &DashboardQuery) -> Result<Self, std::io::Error> {
+            let now = chrono::Utc::now().timestamp();
+            let units = data::load_tenant_units(tenant)?;
+            let users: Vec<User> = data::read_tenant_users(tenant)?
+                .into_iter()
+                .filter(|f| f.archived.is_none() && f.profile.status == CadetStatus::Active)
+                .collect();
+
+            let mut groups: Vec<(Option<String>, Vec<&User>)> = vec![];
+            for user in users.iter() {
+                let flight = user
+                    .flight
+                    .as_ref()
+                    .and_then(|f| data::flight_of(&units, f));
+                match groups.iter_mut().find(|(f, _)| *f == flight) {
+                    Some((_, t)) => t.push(user),
+                    None => groups.push((flight, vec![user])),
+                }
+            }
+            groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+            let cutoff = now.saturating_sub(query.stale_weeks.saturating_mul(7 * 86_400));
+            let mut overdue: Vec<OverdueCadet> = users
+                .iter()
+                .map(|f| (f, f.counted_inspections().filter_map(|i| i.date).max()))
+                .filter(|(_, last)| last.is_none_or(|t| t < cutoff))
+                .map(|(f, last_inspected)| OverdueCadet {
+                    user_uuid: f.uuid.clone(),
+                    name: f.username.clone(),
+                    first_name: f.profile.first_name.clone(),
+                    last_name: f.profile.last_name.clone(),
+                    flight: f.flight.clone(),
+                    last_inspected,
+                })
+                .collect();
+            overdue.sort_by_key(|f| f.last_inspected);
+
+            Ok(Self {
+                generated_at: now,
+                squadron: GroupStats::new(None, &users.iter().collect::<Vec<_>>(), query),
+                flights: groups
+                    .into_iter()
+                    .map(|(flight, cadets)| GroupStats::new(flight, &cadets, query))
+                    .collect(),
+                overdue,
+            })
+        }
+    }
+
+    /// Longest a dashboard is served from the cache, overdue cadets depend on the current time
+    const CACHE_SECONDS: i64 = 300;
+    const CACHE_ENTRIES: usize = 32;
+
+    struct CachedDashboard {
+        tenant: String,
+        query: DashboardQuery,
+        generation: u64,
+        dashboard: Dashboard,
+    }
+
+    static DASHBOARD_CACHE: Mutex<Vec<CachedDashboard>> = Mutex::new(vec![]);
+
+    /// Builds the dashboard for a tenant, reusing a recent result until a cadet, unit or event
+    /// changes
+    pub fn dashboard(tenant: &str, query: &DashboardQuery) -> Result<Dashboard, std::io::Error> {
+        let generation = data::index_generation();
+        let now = chrono::Utc::now().timestamp();
+
+        {
+            let mut cache = DASHBOARD_CACHE
+                .lock()
+                .expect("Dashboard cache lock poisoned");
+            cache.retain(|f| {
+                f.generation == generation && now - f.dashboard.generated_at < CACHE_SECONDS
+            });
+            if let Some(t) = cache
+                .iter()
+                .find(|f| f.tenant == tenant && &f.query == query)
+            {
+                return Ok(t.dashboard.clone());
+            }
+        }
+
+        // Built without holding the lock so one slow dashboard does not block every other tenant
+        let dashboard = Dashboard::new(tenant, query)?;
+
+        let mut cache = DASHBOARD_CACHE
+            .lock()
+            .expect("Dashboard cache lock poisoned");
+        if data::index_generation() != generation
+            || cache
+                .iter()
+                .any(|f| f.tenant == tenant && &f.query == query)
+        {
+            return Ok(dashboard);
+        }
+        if cache.len() >= CACHE_ENTRIES {
+            cache.remove(0);
+        }
+        cache.push(CachedDashboard {
+            tenant: tenant.to_string(),
+            query: query.clone(),
+            generation,
+            dashboard: dashboard.clone(),
+        });
+
+        Ok(dashboard)
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Standing {
+        /// Flights that cannot be separated by any tie breaker share a rank
+        rank: usize,
+        flight: String,
+        cadets: usize,
+        /// Cadets with at least one inspection in the period
+        inspected: usize,
+        /// Average of each cadet's mean score, cadets that were not inspected count as zero
+        average: f32,
+        /// Percentage of event attendance records marked present or late, `None` without any
+        attendance: Option<f32>,
+        /// `average` scaled by `attendance`
+        points: f32,
+        median: Option<f32>,
+        best_score: Option<f32>,
+    }
+
+    impl Standing {
+        fn new(
+            flight: String,
+            cadets: &[&User],
+            competition: &Competition,
+            events: &[Event],
+        ) -> Self {
+            let mut cadet_averages = vec![];
+            let mut scores = vec![];
+            for cadet in cadets {
+                let percentages: Vec<f32> = cadet
+                    .counted_inspections()
+                    .filter(|f| f.date.is_some_and(|t| competition.contains(t)))
+                    .filter_map(|f| f.get_score().percentage())
+                    .collect();
+                if let Some(t) = mean(&percentages) {
+                    cadet_averages.push(t);
+                }
+                scores.extend(percentages);
+            }
+
+            let records: Vec<&Attendance> = cadets
+                .iter()
+                .flat_map(|f| f.attendance.iter())
+                .filter(|f| events.iter().any(|e| e.id == f.event))
+                .filter(|f| f.status != AttendanceStatus::Excused)
+                .collect();
+            let attendance = match records.len() {
+                0 => None,
+                n => Some(
+                    records
+                        .iter()
+                        .filter(|f| {
+                            matches!(f.status, AttendanceStatus::Present | AttendanceStatus::Late)
+                        })
+                        .count() as f32
+                        / n as f32
+                        * 100.0,
+                ),
+            };
+
+            let average = match cadets.len() {
+                0 => 0.0,
+                n => cadet_averages.iter().fold(0.0, |a, b| a + b) / n as f32,
+            };
+
+            Self {
+                rank: 0,
+                flight,
+                cadets: cadets.len(),
+                inspected: cadet_averages.len(),
+                average,
+                attendance,
+                points: average * attendance.unwrap_or(100.0) / 100.0,
+                best_score: scores.iter().copied().max_by(f32::total_cmp),
+                median: median(&mut scores),
+            }
+        }
+
+        /// Value used to compare flights for a tie breaker, higher is better
+        fn tie_value(&self, tie_breaker: TieBreaker) -> f32 {
+            match tie_breaker {
+                TieBreaker::Attendance => self.attendance.unwrap_or(0.0),
+                TieBreaker::Participation => match self.cadets {
+                    0 => 0.0,
+                    n => self.inspected as f32 / n as f32 * 100.0,
+                },
+                TieBreaker::Median => self.median.unwrap_or(0.0),
+                TieBreaker::BestScore => self.best_score.unwrap_or(0.0),
+            }
+        }
+
+        /// Orders by points then each tie breaker, values are compared to two decimal places so
+        /// rounding noise does not separate flights
+        fn compare(&self, other: &Self, tie_breakers: &[TieBreaker]) -> std::cmp::Ordering {
+            let round = |f: f32| (f * 100.0).round() as i64;
+
+            tie_breakers.iter().fold(
+                round(other.points).cmp(&round(self.points)),
+                |ordering, t| {
+                    ordering.then(round(other.tie_value(*t)).cmp(&round(self.tie_value(*t))))
+                },
+            )
+        }
+    }
+
+    #[derive(Serialize, Debug, Clone)]
+    pub struct Standings {
+        competition: Competition,
+        /// Best first
+        standings: Vec<Standing>,
+    }
+
+    impl Standings {
+        /// The standings of a finished period are saved the first time they are worked out and
+        /// read back from then on
+        pub fn new(mut competition: Competition) -> Result<Self, std::io::Error> {
+            if let Some(standings) = competition.results.take() {
+                return Ok(Self {
+                    competition,
+                    standings,
+                });
+            }
+
+            let standings = Self::compute(&competition)?;
+            if competition.end <= chrono::Utc::now().timestamp() {
+                competition.results = Some(standings.clone());
+                competition.save()?;
+                competition.results = None;
+            }
+
+            Ok(Self {
+                competition,
+                standings,
+            })
+        }
+
+        fn compute(competition: &Competition) -> Result<Vec<Standing>, std::io::Error> {
+            let units = data::load_tenant_units(&competition.tenant)?;
+            let events: Vec<Event> = data::load_events(&competition.tenant)?
+                .into_iter()
+                .filter(|f| competition.contains(f.date))
+                .collect();
+            // Cadets archived or made inactive since the period started still count for the
+            // inspections they had during it
+            let users: Vec<User> = data::read_tenant_users(&competition.tenant)?
+                .into_iter()
+                .filter(|f| f.archived.is_none_or(|t| t >= competition.start))
+                .filter(|f| {
+                    f.profile.status == CadetStatus::Active
+                        || f.counted_inspections()
+                            .any(|i| i.date.is_some_and(|t| competition.contains(t)))
+                })
+                .collect();
+
+            let mut standings: Vec<Standing> = units
+                .iter()
+                .filter(|f| f.kind == UnitKind::Flight)
+                .map(|flight| {
+                    let cadets: Vec<&User> = users
+                        .iter()
+                        .filter(|f| {
+                            f.flight.as_ref().and_then(|t| data::flight_of(&units, t))
+                                == Some(flight.id.clone())
+                        })
+                        .collect();
+                    Standing::new(flight.id.clone(), &cadets, competition, &events)
+                })
+                // Flights without cadets have nothing to compete with
+                .filter(|f| f.cadets > 0)
+                .collect();
+
+            rank_standings(&mut standings, &competition.tie_breakers);
+            Ok(standings)
+        }
+    }
+
+    /// Orders best first and numbers the ranks, flights tied on every breaker share a rank and the
+    /// next flight's rank skips past them
+    fn rank_standings(standings: &mut [Standing], tie_breakers: &[TieBreaker]) {
+        standings.sort_by(|a, b| a.compare(b, tie_breakers));
+        for index in 0..standings.len() {
+            standings[index].rank = match index {
+                0 => 1,
+                _ if standings[index - 1]
+                    .compare(&standings[index], tie_breakers)
+                    .is_eq() =>
+                {
+                    standings[index - 1].rank
+                }
+                _ => index + 1,
+            };
+        }
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct PeriodResult {
+        competition: Competition,
+        /// Every flight ranked first, more than one when tied
+        winners: Vec<String>,
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct LeaderboardEntry {
+        flight: String,
+        wins: usize,
+        /// Finishes in the top three, including wins
+        podiums: usize,
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct Leaderboard {
+        /// Finished periods, newest first
+        periods: Vec<PeriodResult>,
+        /// Most wins first, then most podiums
+        flights: Vec<LeaderboardEntry>,
+    }
+
+    impl Leaderboard {
+        pub fn new(tenant: &str) -> Result<Self, std::io::Error> {
+            let now = chrono::Utc::now().timestamp();
+
+            let mut periods = vec![];
+            let mut flights: Vec<LeaderboardEntry> = vec![];
+            for competition in data::load_competitions(tenant)? {
+                if competition.end > now {
+                    continue;
+                }
+
+                let standings = Standings::new(competition)?;
+                for standing in standings.standings.iter().filter(|f| f.rank <= 3) {
+                    let entry = match flights.iter().position(|f| f.flight == standing.flight) {
+                        Some(t) => &mut flights[t],
+                        None => {
+                            flights.push(LeaderboardEntry {
+                                flight: standing.flight.clone(),
+                                wins: 0,
+                                podiums: 0,
+                            });
+                            flights.last_mut().expect("Just pushed")
+                        }
+                    };
+                    entry.podiums += 1;
+                    entry.wins += (standing.rank == 1) as usize;
+                }
+
+                periods.push(PeriodResult {
+                    winners: standings
+                        .standings
+                        .iter()
+                        .filter(|f| f.rank == 1)
+                        .map(|f| f.flight.clone())
+                        .collect(),
+                    competition: standings.competition,
+                });
+            }
+            flights.sort_by_key(|f| (std::cmp::Reverse(f.wins), std::cmp::Reverse(f.podiums)));
+
+            Ok(Self { periods, flights })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn window_change_compares_the_last_two_windows() {
+            assert_eq!(window_change(&[50.0, 50.0, 70.0, 70.0], 2), Some(20.0));
+            // Older values outside both windows are ignored
+            assert_eq!(window_change(&[0.0, 90.0, 80.0, 60.0], 1), Some(-20.0));
+        }
+
+        #[test]
+        fn window_change_needs_two_full_windows() {
+            assert_eq!(window_change(&[50.0, 60.0, 70.0], 2), None);
+            assert_eq!(window_change(&[50.0, 60.0], 0), None);
+            assert_eq!(window_change(&[], 1), None);
+        }
+
+        #[test]
+        fn window_change_does_not_overflow() {
+            assert_eq!(window_change(&[50.0, 60.0], usize::MAX), None);
+            assert_eq!(window_change(&[50.0, 60.0], usize::MAX / 2 + 1), None);
+        }
+
+        fn standing(
+            flight: &str,
+            points: f32,
+            attendance: Option<f32>,
+            inspected: usize,
+        ) -> Standing {
+            Standing {
+                rank: 0,
+                flight: flight.into(),
+                cadets: 4,
+                inspected,
+                average: points,
+                attendance,
+                points,
+                median: None,
+                best_score: None,
+            }
+        }
+
+        fn ranks(standings: &[Standing]) -> Vec<(&str, usize)> {
+            standings
+                .iter()
+                .map(|f| (f.flight.as_str(), f.rank))
+                .collect()
+        }
+
+        #[test]
+        fn tie_breakers_separate_equal_points_in_order() {
+            let mut standings = vec![
+                standing("Morgan", 80.0, Some(90.0), 2),
+                standing("Beddoe", 80.0, Some(90.0), 4),
+                standing("Spear", 80.0, Some(95.0), 1),
+                standing("Bell", 85.0, None, 0),
+            ];
+            rank_standings(
+                &mut standings,
+                &[TieBreaker::Attendance, TieBreaker::Participation],
+            );
+
+            assert_eq!(
+                ranks(&standings),
+                vec![("Bell", 1), ("Spear", 2), ("Beddoe", 3), ("Morgan", 4)]
+            );
+        }
+
+        #[test]
+        fn flights_tied_on_every_breaker_share_a_rank() {
+            let mut standings = vec![
+                standing("Morgan", 80.0, Some(90.0), 2),
+                // Differs only by rounding noise
+                standing("Beddoe", 80.001, Some(90.0), 2),
+                standing("Spear", 70.0, None, 4),
+            ];
+            rank_standings(&mut standings, &[TieBreaker::Attendance]);
+
+            assert_eq!(
+                ranks(&standings),
+                vec![("Morgan", 1), ("Beddoe", 1), ("Spear", 3)]
+            );
+        }
+
+        #[test]
+        fn saved_results_are_returned_as_they_were() {
+            let mut saved = vec![
+                standing("Morgan", 50.0, None, 1),
+                standing("Beddoe", 60.0, None, 1),
+            ];
+            saved[0].rank = 1;
+            saved[1].rank = 2;
+            let competition = Competition {
+                id: "period".into(),
+                name: "Period".into(),
+                start: 0,
+                end: 1,
+                tie_breakers: vec![],
+                tenant: data::default_tenant(),
+                results: Some(saved),
+            };
+
+            // Not ranked again, on points Beddoe would come first
+            let standings = Standings::new(competition).unwrap();
+            assert_eq!(
+                ranks(&standings.standings),
+                vec![("Morgan", 1), ("Beddoe", 2)]
+            );
+        }
+    }
+}
+
+/// Inspection results as spreadsheets, for officers that keep their own records
+pub mod export {
+    use serde::Deserialize;
+
+    use super::data::{self, Criteria, Exemption};
+
+    #[derive(Deserialize, Debug, Default)]
+    pub struct ExportQuery {
+        /// Only inspections on or after this timestamp
+        pub from: Option<i64>,
+        /// Only inspections before this timestamp
+        pub to: Option<i64>,
+        /// Only cadets in this unit, including its sections
+        pub flight: Option<String>,
+        /// Only inspections using the template with this name
+        pub template: Option<String>,
+    }
+
+    pub enum Cell {
+        Text(String),
+        Number(f64),
+        Empty,
+    }
+
+    /// One row per inspection and one column per criterion, voided inspections are left out
+    pub struct Table {
+        pub headers: Vec<String>,
+        pub rows: Vec<Vec<Cell>>,
+    }
+
+    const FIXED_HEADERS: [&str; 13] = [
+        "cadet_uuid",
+        "username",
+        "first_name",
+        "last_name",
+        "rank",
+        "flight",
+        "inspection_id",
+        "template",
+        "date",
+        "inspector",
+        "score",
+        "out_of",
+        "percentage",
+    ];
+
+    fn text(value: &Option<String>) -> Cell {
+        match value {
+            Some(t) => Cell::Text(t.clone()),
+            None => Cell::Empty,
+        }
+    }
+
+    /// The result of a criterion as written in its column
+    fn criterion_cell(criteria: &Criteria) -> Cell {
+        match (criteria, criteria.exemption()) {
+            (_, Some(Exemption::NotApplicable)) => Cell::Text("N/A".into()),
+            (_, Some(Exemption::Excused)) => Cell::Text("Excused".into()),
+            (Criteria::PassFail(t), None) => match t.state {
+                Some(true) => Cell::Text("Pass".into()),
+                Some(false) => Cell::Text("Fail".into()),
+                None => Cell::Empty,
+            },
+            (Criteria::Graded(t), None) => match t.state {
+                Some(state) => Cell::Number(state.into()),
+                None => Cell::Empty,
+            },
+            (Criteria::Comment(_), None) => Cell::Empty,
+        }
+    }
+
+    impl Table {
+        pub fn new(tenant: &str, query: &ExportQuery) -> Result<Self, std::io::Error> {
+            let units = data::load_tenant_units(tenant)?;
+            let mut users: Vec<data::User> = data::read_tenant_users(tenant)?
+                .into_iter()
+                .filter(|f| match &query.flight {
+                    Some(flight) => f
+                        .flight
+                        .as_ref()
+                        .is_some_and(|t| data::unit_within(&units, t, flight)),
+                    None => true,
+                })
+                .collect();
+            users.sort_by(|a, b| {
+                (&a.flight, &a.profile.last_name, &a.profile.first_name).cmp(&(
+                    &b.flight,
+                    &b.profile.last_name,
+                    &b.profile.first_name,
+                ))
+            });
+
+            let mut inspections = vec![];
+            for user in users.iter() {
+                let mut selected: Vec<&data::Inspection> = user
+                    .counted_inspections()
+                    .filter(|f| match f.date {
+                        Some(date) => {
+                            query.from.is_none_or(|t| date >= t)
+                                && query.to.is_none_or(|t| date < t)
+                        }
+                        None => query.from.is_none() && query.to.is_none(),
+                    })
+                    .filter(|f| query.template.as_ref().is_none_or(|t| &f.name == t))
+                    .collect();
+                selected.sort_by_key(|f| f.date);
+                inspections.extend(selected.into_iter().map(|f| (user, f)));
+            }
+
+            // Criteria columns in the order they are first seen
+            let mut criteria: Vec<String> = vec![];
+            for name in inspections
+                .iter()
+                .flat_map(|(_, f)| f.criteria.iter())
+                .filter_map(|f| f.category_name())
+            {
+                if !criteria.iter().any(|f| f == name) {
+                    criteria.push(name.to_string());
+                }
+            }
+
+            let rows = inspections
+                .into_iter()
+                .map(|(user, inspection)| {
+                    let score = inspection.get_score();
+                    let mut row = vec![
+                        Cell::Text(user.uuid.clone()),
+                        text(&user.username),
+                        text(&user.profile.first_name),
+                        text(&user.profile.last_name),
+                        text(&user.profile.rank),
+                        text(&user.flight),
+                        text(&inspection.id),
+                        Cell::Text(inspection.name.clone()),
+                        match inspection
+                            .date
+                            .and_then(|f| chrono::DateTime::from_timestamp(f, 0))
+                        {
+                            Some(t) => Cell::Text(t.format("%Y-%m-%d").to_string()),
+                            None => Cell::Empty,
+                        },
+                        text(&inspection.inspector),
+                        Cell::Number(score.score().into()),
+                        Cell::Number(score.out_of().into()),
+                        // Rounded so the f32 noise does not end up in the spreadsheet
+                        match score.percentage() {
+                            Some(t) => Cell::Number((f64::from(t) * 100.0).round() / 100.0),
+                            None => Cell::Empty,
+                        },
+                    ];
+                    row.extend(criteria.iter().map(|name| {
+                        match inspection
+                            .criteria
+                            .iter()
+                            .find(|f| f.category_name() == Some(name))
+                        {
+                            Some(t) => criterion_cell(t),
+                            None => Cell::Empty,
+                        }
+                    }));
+                    row
+                })
+                .collect();
+
+            Ok(Self {
+                headers: FIXED_HEADERS
+                    .iter()
+                    .map(|f| f.to_string())
+                    .chain(criteria)
+                    .collect(),
+                rows,
+            })
+        }
+
+        pub fn to_csv(&self) -> Result<Vec<u8>, std::io::Error> {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            writer.write_record(&self.headers)?;
+            for row in self.rows.iter() {
+                writer.write_record(row.iter().map(|f| match f {
+                    // Spreadsheets run text starting with these as a formula, usernames and notes
+                    // come from cadets and staff so they are quoted instead
+                    Cell::Text(t) if t.starts_with(['=', '+', '-', '@', '\t', '\r']) => {
+                        format!("'{t}")
+                    }
+                    Cell::Text(t) => t.clone(),
+                    Cell::Number(t) => t.to_string(),
+                    Cell::Empty => String::new(),
+                }))?;
+            }
+
+            writer.into_inner().map_err(|e| e.into_error())
+        }
+
+        pub fn to_xlsx(&self) -> Result<Vec<u8>, std::io::Error> {
+            let mut workbook = rust_xlsxwriter::Workbook::new();
+            self.write_sheet(workbook.add_worksheet())
+                .map_err(std::io::Error::other)?;
+
+            workbook.save_to_buffer().map_err(std::io::Error::other)
+        }
+
+        fn write_sheet(
+            &self,
+            worksheet: &mut rust_xlsxwriter::Worksheet,
+        ) -> Result<(), rust_xlsxwriter::XlsxError> {
+            let bold = rust_xlsxwriter::Format::new().set_bold();
+            for (col, header) in self.headers.iter().enumerate() {
+                worksheet.write_string_with_format(0, col as u16, header, &bold)?;
+            }
+
+            for (row, cells) in self.rows.iter().enumerate() {
+                let row = row as u32 + 1;
+                for (col, cell) in cells.iter().enumerate() {
+                    match cell {
+                        Cell::Text(t) => worksheet.write_string(row, col as u16, t)?,
+                        Cell::Number(t) => worksheet.write_number(row, col as u16, *t)?,
+                        Cell::Empty => worksheet,
+                    };
+                }
+            }
+            worksheet.set_freeze_panes(1, 0)?;
+
+            Ok(())
+        }
+    }
 }
@@ -1,13 +1,19 @@
 mod auth;
 mod database;
+mod printing;
 
 use crate::auth::database::Token;
 use actix_cors::Cors;
+use actix_multipart::Multipart;
 use actix_web::middleware::{Logger, NormalizePath};
-use actix_web::{get, post, web, web::scope, App, HttpResponse, HttpServer, Result};
+use actix_web::{get, post, web, web::scope, App, HttpRequest, HttpResponse, HttpServer, Result};
 use actix_web_lab::web::spa;
 
-use database::data::{self, index_users, read_user_index, Flight};
+use database::analytics;
+use database::attachments;
+use database::data::{self, index_users, read_user_index};
+use database::export;
+use printing::{cards, qr, reports};
 
 use futures_util::StreamExt as _;
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
@@ -28,46 +34,115 @@ async fn get_user(mut payload: web::Payload) -> Result<HttpResponse> {
     }
     .map_err(|_| actix_web::error::ErrorBadRequest("could not parse request"))?;
 
-    let user = data::User::read_from_database(user_id)
-        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
-
-    Ok(HttpResponse::Found().body(
-        serde_json::to_string(&user)
-            .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?,
-    ))
+    // Anyone holding the QR code can read this, so only the cadet view is returned
+    Ok(HttpResponse::Found().body(serde_json::to_string(&view_for_card(&user_id)?)?))
 }
 
-#[get("/user/img/{user_id}.svg")]
-async fn get_qrcode_for_user(path: web::Path<String>) -> Result<HttpResponse> {
-    use qrcode::QrCode;
-
-    let user_id = path.into_inner();
-    match data::User::read_from_database(user_id.clone()).is_ok() {
-        true => {
-            let qr_code = QrCode::with_error_correction_level(
-                format!("https://uniform.952aircadets.ca/u/{}", user_id).into_bytes(),
-                qrcode::EcLevel::L,
-            )
-            .unwrap();
-            // .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse uuid"))?;
-            Ok(HttpResponse::Ok().content_type("image/svg+xml").body(
-                qr_code
-                    .render::<qrcode::render::svg::Color>()
-                    .min_dimensions(300, 300)
-                    .build(),
-            ))
+/// Looks up the cadet behind a scanned QR code, see [`data::resolve_card`]
+fn cadet_from_card(code: &str) -> Result<data::User> {
+    data::resolve_card(code).map_err(|e| match e {
+        data::CardError::Invalid => actix_web::error::ErrorNotFound("User not found"),
+        data::CardError::Revoked => {
+            actix_web::error::ErrorGone("This card has been replaced, ask staff for a new one")
         }
-        false => Err(actix_web::error::ErrorBadRequest("User not found")),
+        data::CardError::Expired => actix_web::error::ErrorGone(
+            "Old QR codes are no longer accepted, ask staff for a new card",
+        ),
+    })
+}
+
+/// Read-only view of a cadet's own record, reached by scanning their QR code
+#[get("/cadet-view/{user_id}")]
+async fn cadet_view(path: web::Path<String>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&view_for_card(&path.into_inner())?)?))
+}
+
+/// The privacy-safe view of the cadet behind a QR code, as long as their squadron allows it
+fn view_for_card(code: &str) -> Result<data::CadetView> {
+    let user = cadet_from_card(code)?;
+
+    if !data::find_tenant(&user.tenant)?.cadet_view {
+        return Err(actix_web::error::ErrorForbidden(
+            "Cadet view is disabled for this squadron",
+        ));
     }
+
+    Ok(data::CadetView::from(&user))
 }
 
-#[post("validate_uuid/{uuid}")]
-async fn validate_uuid(path: web::Path<String>) -> Result<HttpResponse> {
-    use std::path::Path;
+/// Renders a cadet's QR code, answering with `304 Not Modified` when the client already has it
+fn qrcode_for_user(
+    req: &HttpRequest,
+    user_id: String,
+    format: qr::Format,
+    options: &qr::Options,
+) -> Result<HttpResponse> {
+    options
+        .validate()
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    // Only renders signed codes that are still valid, so no new card is ever printed with a bare uuid
+    if !data::card_is_signed(&user_id) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Only signed card codes can be rendered",
+        ));
+    }
+    let user = cadet_from_card(&user_id)?;
+    let tenant = data::find_tenant(&user.tenant)?;
+    let logo = match options.logo {
+        true => Some(
+            tenant
+                .qr_logo()?
+                .ok_or_else(|| actix_web::error::ErrorNotFound("Squadron has no logo"))?,
+        ),
+        false => None,
+    };
+
+    let url = tenant.cadet_url(user_id.trim());
+    let etag = qr::etag(&url, format, options, logo.as_deref());
+    let cached = req
+        .headers()
+        .get("If-None-Match")
+        .and_then(|f| f.to_str().ok())
+        .is_some_and(|f| f.split(',').any(|t| t.trim() == etag));
+    if cached {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(("ETag", etag))
+            .finish());
+    }
+
+    let rendered = qr::render(&url, format, options, logo.as_deref())
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(rendered.content_type)
+        .insert_header(("ETag", rendered.etag.clone()))
+        // The code inside is enough to open the cadet's record, so shared caches must not keep it
+        .insert_header(("Cache-Control", "private, max-age=3600"))
+        .body(rendered.body.clone()))
+}
 
-    let user_id = path.into_inner();
+#[get("/user/img/{user_id}.svg")]
+async fn get_qrcode_for_user(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<qr::Options>,
+) -> Result<HttpResponse> {
+    qrcode_for_user(&req, path.into_inner(), qr::Format::Svg, &query)
+}
+
+#[get("/user/img/{user_id}.png")]
+async fn get_qrcode_png_for_user(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<qr::Options>,
+) -> Result<HttpResponse> {
+    qrcode_for_user(&req, path.into_inner(), qr::Format::Png, &query)
+}
 
-    let exitst = Path::new(format!("database/users/{user_id}.json").as_str()).exists();
+#[post("validate_uuid/{uuid}")]
+async fn validate_uuid(path: web::Path<String>) -> Result<HttpResponse> {
+    let exitst = cadet_from_card(&path.into_inner()).is_ok();
     let response = match exitst {
         true => "true",
         false => "false",
@@ -76,23 +151,166 @@ async fn validate_uuid(path: web::Path<String>) -> Result<HttpResponse> {
     Ok(HttpResponse::Ok().body(response))
 }
 
+#[derive(Deserialize)]
+struct TenantQuery {
+    tenant: Option<String>,
+}
+
+impl TenantQuery {
+    /// The requested tenant, falling back to the default tenant when none is given
+    fn tenant(&self) -> Result<String> {
+        let tenant = self.tenant.clone().unwrap_or_else(data::default_tenant);
+        data::find_tenant(&tenant)
+            .map_err(|_| actix_web::error::ErrorNotFound("Tenant not found"))?;
+        Ok(tenant)
+    }
+}
+
+/// Returns the tenant of the user a token was issued to
+fn tenant_of(token: &Token) -> Result<String> {
+    token
+        .tenant()
+        .ok_or_else(|| actix_web::error::ErrorForbidden("Invalid Token"))
+}
+
+#[get("/tenant")]
+async fn serve_tenant(query: web::Query<TenantQuery>) -> Result<HttpResponse> {
+    Ok(
+        HttpResponse::Ok().body(serde_json::to_string(&data::find_tenant(
+            &query.tenant()?,
+        )?)?),
+    )
+}
+
+#[derive(Deserialize)]
+struct SaveTenant {
+    token: Token,
+    tenant: data::Tenant,
+}
+
+/// Updates the branding of the admin's own tenant, new tenants are added to `tenants.json`
+#[post("/tenant/save")]
+async fn save_tenant(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: SaveTenant = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    let mut tenant = request.tenant;
+    tenant.id = tenant_of(&request.token)?;
+
+    // The base url is allowed through CORS, so it can only be changed by editing tenants.json
+    let mut tenants = data::load_tenants()?;
+    let existing = tenants
+        .iter_mut()
+        .find(|f| f.id == tenant.id)
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Tenant not found"))?;
+    tenant.base_url = std::mem::take(&mut existing.base_url);
+    *existing = tenant;
+    data::write_tenants(&tenants)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Accepts a `multipart/form-data` upload with the fields `token` and `file`, in that order, and
+/// uses the image as the tenant's QR logo
+#[post("/tenant/logo")]
+async fn upload_tenant_logo(mut payload: Multipart) -> Result<HttpResponse> {
+    let mut token: Option<Token> = None;
+    let mut file: Option<(String, web::BytesMut)> = None;
+
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let name = field.name().unwrap_or_default().to_string();
+        let content_type = field.content_type().map(|f| f.essence_str().to_string());
+
+        if name == "file" && token.is_none() {
+            return Err(actix_web::error::ErrorForbidden(
+                "Token must be sent before the file",
+            ));
+        }
+
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = field.next().await {
+            let item = item?;
+            if bytes.len() + item.len() > attachments::MAX_SIZE {
+                return Err(actix_web::error::ErrorPayloadTooLarge("Logo too large"));
+            }
+            bytes.extend_from_slice(&item);
+        }
+
+        match name.as_str() {
+            "token" => {
+                let t: Token = serde_json::de::from_slice(&bytes)?;
+                check_admin(&t)?;
+                token = Some(t);
+            }
+            "file" => file = content_type.map(|t| (t, bytes)),
+            _ => (),
+        }
+    }
+
+    let (Some(token), Some((content_type, bytes))) = (token, file) else {
+        return Err(actix_web::error::ErrorBadRequest("Missing field"));
+    };
+
+    if !attachments::is_supported(&content_type) {
+        return Err(actix_web::error::ErrorUnsupportedMediaType(
+            "Only JPEG and PNG images are supported",
+        ));
+    }
+
+    data::find_tenant(&tenant_of(&token)?)?
+        .set_qr_logo(&content_type, &bytes)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Could not read image"))?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// A cadet's current signed card code and the link their QR code opens
+#[derive(Serialize)]
+struct CardCode {
+    uuid: String,
+    code: String,
+    url: String,
+}
+
+impl CardCode {
+    fn new(user: &data::User) -> Result<Self> {
+        let code = data::card_code(user);
+        Ok(Self {
+            uuid: user.uuid.clone(),
+            url: data::find_tenant(&user.tenant)?.cadet_url(&code),
+            code,
+        })
+    }
+}
+
 #[get("/newuser/")]
-async fn generate_user() -> Result<HttpResponse> {
-    let new_user = data::User::new();
+async fn generate_user(query: web::Query<TenantQuery>) -> Result<HttpResponse> {
+    let new_user = data::User::new(query.tenant()?);
     new_user.push_to_data_base();
 
+    let code = data::card_code(&new_user);
     let response = HttpResponse::Found()
-        .append_header(("location", format!("/u/{}", new_user.uuid)))
-        .body(new_user.uuid);
+        .append_header(("location", format!("/u/{}", code)))
+        .body(code);
     Ok(response)
 }
 
 #[post("/newuser/")]
-async fn post_generate_user() -> Result<HttpResponse> {
-    let new_user = data::User::new();
+async fn post_generate_user(query: web::Query<TenantQuery>) -> Result<HttpResponse> {
+    let new_user = data::User::new(query.tenant()?);
     new_user.push_to_data_base();
 
-    let response = HttpResponse::Found().body(new_user.uuid);
+    let response = HttpResponse::Found().body(serde_json::to_string(&CardCode::new(&new_user)?)?);
     Ok(response)
 }
 
@@ -117,13 +335,24 @@ async fn add_inspection_to_user(mut payload: web::Payload) -> Result<HttpRespons
 
     match request.token.check_token_validy() {
         auth_database::TokenResponse::Valid => {
-            let mut inspectee = data::User::read_from_database(request.user_uuid)
+            let tenant = tenant_of(&request.token)?;
+            let mut inspectee = data::User::read_for_tenant(request.user_uuid, &tenant)
                 .map_err(|_| actix_web::error::ErrorNotFound("Requested Auth User Not Found"))?;
 
-            inspectee.push_inspection(request.inspection_to_post);
+            request
+                .inspection_to_post
+                .validate(&data::load_deficiency_codes(&tenant)?)
+                .map_err(actix_web::error::ErrorBadRequest)?;
+            check_event_open(&request.inspection_to_post, &inspectee, &tenant)?;
+
+            let mut inspection = request.inspection_to_post;
+            inspection.inspector = Some(request.token.user_uuid().to_string());
+
+            inspectee.push_inspection(inspection);
             inspectee.push_to_data_base();
 
-            Ok(actix_web::HttpResponse::Ok().body(serde_json::to_string(&read_user_index()?)?))
+            Ok(actix_web::HttpResponse::Ok()
+                .body(serde_json::to_string(&read_user_index(&tenant)?)?))
         }
         auth_database::TokenResponse::Invalid => {
             Err(actix_web::error::ErrorForbidden("Invalid Token"))
@@ -136,60 +365,49 @@ async fn add_inspection_to_user(mut payload: web::Payload) -> Result<HttpRespons
     // Load the user and append the inspection
 }
 
-#[get("/inspections.json")]
-async fn return_inspections() -> Result<HttpResponse> {
-    let mut inspections = data::load_inspection_list()
-        .map_err(|_| actix_web::error::ErrorInternalServerError("Internal Error Occured"))?;
-
-    inspections.iter_mut().for_each(|f| f.compute_score());
-
-    Ok(HttpResponse::Found()
-        .body(serde_json::ser::to_string(&inspections).expect("This should always work")))
-}
-
-use auth::database::{self as auth_database, TokenResponse};
-
-#[derive(Serialize, Deserialize, Debug)]
-struct UserLogin {
-    username: String,
-    password: String,
+#[derive(Deserialize)]
+struct SaveDraft {
+    token: Token,
+    user_uuid: String,
+    inspection: data::Inspection,
 }
 
-#[post("/auth/login")]
-async fn login(mut payload: web::Payload) -> Result<HttpResponse> {
-    let request: UserLogin = serde_json::de::from_str({
+#[post("/drafts/save")]
+async fn save_draft(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: SaveDraft = serde_json::de::from_str({
         let mut bytes = web::BytesMut::new();
         while let Some(item) = payload.next().await {
             bytes.extend_from_slice(&item?);
         }
-
         String::from_utf8(bytes.to_vec())
             .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
             .as_str()
     })?;
 
-    Ok(
-        match auth_database::User::get_user(request.username, request.password) {
-            None => HttpResponse::NotFound().finish(),
-            Some(mut t) => {
-                t.accosiate_token();
-                t.clone().push_to_disk();
-                HttpResponse::Found().body(
-                    serde_json::to_string(
-                        t.tokens
-                            .last()
-                            .expect("We just added a token so we should be good here"),
-                    )
-                    .unwrap(),
-                )
-            }
-        },
-    )
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let user = data::User::read_for_tenant(request.user_uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    data::Draft {
+        inspector: request.token.user_uuid().to_string(),
+        user_uuid: user.uuid,
+        inspection: request.inspection,
+        updated_at: chrono::Utc::now().timestamp(),
+    }
+    .save()?;
+
+    Ok(HttpResponse::Ok().finish())
 }
 
-#[post("/auth/signup")]
-async fn signup(mut payload: web::Payload) -> Result<HttpResponse> {
-    let request: UserLogin = serde_json::de::from_str({
+#[post("/drafts")]
+async fn list_drafts(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: Token = serde_json::de::from_str({
         let mut bytes = web::BytesMut::new();
         while let Some(item) = payload.next().await {
             bytes.extend_from_slice(&item?);
@@ -199,23 +417,24 @@ async fn signup(mut payload: web::Payload) -> Result<HttpResponse> {
             .as_str()
     })?;
 
-    let user = auth_database::User::new(request.username, request.password)
-        .map_err(|_| actix_web::error::ErrorLocked("Username taken"))?;
-
-    user.push_to_disk();
-
-    Ok(HttpResponse::Ok().finish())
+    match request.check_token_validy() {
+        TokenResponse::Valid => Ok(HttpResponse::Ok().body(serde_json::ser::to_string(
+            &data::Draft::list(request.user_uuid())?,
+        )?)),
+        TokenResponse::Invalid => Err(actix_web::error::ErrorForbidden("Invalid Token")),
+        TokenResponse::Expired => Err(actix_web::error::ErrorForbidden("Expired Token")),
+    }
 }
 
 #[derive(Deserialize)]
-struct UserClaim {
-    uuid: String,
-    username: String,
+struct DraftRequest {
+    token: Token,
+    user_uuid: String,
 }
 
-#[post("/claim-user")]
-async fn claim_user(mut payload: web::Payload) -> Result<HttpResponse> {
-    let request: UserClaim = serde_json::de::from_str({
+#[post("/drafts/discard")]
+async fn discard_draft(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: DraftRequest = serde_json::de::from_str({
         let mut bytes = web::BytesMut::new();
         while let Some(item) = payload.next().await {
             bytes.extend_from_slice(&item?);
@@ -224,42 +443,27 @@ async fn claim_user(mut payload: web::Payload) -> Result<HttpResponse> {
             .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
             .as_str()
     })?;
-    let mut user = data::User::read_from_database(request.uuid)
-        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
 
-    match user.username {
-        None => {
-            user.username = Some(request.username);
-            user.push_to_data_base();
-            Ok(HttpResponse::Ok().finish())
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
         }
-        _ => Err(actix_web::error::ErrorForbidden(
-            "Forbiden username already set",
-        )),
-    }
-}
+        TokenResponse::Valid => Ok(()),
+    }?;
 
-#[actix_web::get("/{url:.*}")]
-async fn http_upgrade() -> Result<HttpResponse> {
-    let file_string = fs::read_to_string("../UUIS-backend/https_upgrade.html")
-        .expect("https upgrade screen missing")
-        .replace("\n", "");
+    let user = data::User::read_for_tenant(request.user_uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
 
-    Ok(HttpResponse::Found()
-        .content_type("text/html")
-        .insert_header(("redirect", "https://uniform.952aircadets.ca"))
-        .body(file_string))
-}
+    data::Draft::delete(request.token.user_uuid(), &user.uuid)
+        .map_err(|_| actix_web::error::ErrorNotFound("Draft not found"))?;
 
-#[derive(Deserialize)]
-struct SetFlight {
-    uuid: String,
-    flight: data::Flight,
+    Ok(HttpResponse::Ok().finish())
 }
 
-#[post("/set_flight")]
-async fn set_flight(mut payload: web::Payload) -> Result<HttpResponse> {
-    let request: SetFlight = serde_json::de::from_str({
+/// Validates and scores a draft, records it against the cadet and removes the draft
+#[post("/drafts/finalize")]
+async fn finalize_draft(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: DraftRequest = serde_json::de::from_str({
         let mut bytes = web::BytesMut::new();
         while let Some(item) = payload.next().await {
             bytes.extend_from_slice(&item?);
@@ -269,18 +473,61 @@ async fn set_flight(mut payload: web::Payload) -> Result<HttpResponse> {
             .as_str()
     })?;
 
-    let mut user = data::User::read_from_database(request.uuid)
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let tenant = tenant_of(&request.token)?;
+    let mut inspectee = data::User::read_for_tenant(request.user_uuid, &tenant)
         .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
 
-    user.flight = Some(request.flight);
-    user.push_to_data_base();
+    let draft = data::Draft::read(request.token.user_uuid(), &inspectee.uuid)
+        .map_err(|_| actix_web::error::ErrorNotFound("Draft not found"))?;
 
-    Ok(HttpResponse::Ok().finish())
+    draft
+        .inspection
+        .validate(&data::load_deficiency_codes(&tenant)?)
+        .map_err(actix_web::error::ErrorBadRequest)?;
+    check_event_open(&draft.inspection, &inspectee, &tenant)?;
+
+    let mut inspection = draft.inspection;
+    inspection.inspector = Some(draft.inspector.clone());
+
+    inspectee.push_inspection(inspection);
+    inspectee.push_to_data_base();
+    data::Draft::delete(&draft.inspector, &draft.user_uuid)?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&read_user_index(&tenant)?)?))
 }
 
-#[post("/user_index")]
-async fn user_index(mut payload: web::Payload) -> Result<HttpResponse> {
-    let request: Token = serde_json::de::from_str({
+/// Only the inspector that recorded an inspection or an admin may change it
+fn check_can_change_inspection(token: &Token, inspection: &data::Inspection) -> Result<()> {
+    let is_inspector = inspection.inspector.as_deref() == Some(token.user_uuid());
+    let is_admin = token.role() == Some(auth_database::Role::Admin);
+
+    match is_inspector || is_admin {
+        true => Ok(()),
+        false => Err(actix_web::error::ErrorForbidden(
+            "Only the inspector or an admin may change an inspection",
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+struct AmendInspection {
+    token: Token,
+    user_uuid: String,
+    inspection_id: String,
+    amended: data::Inspection,
+    reason: String,
+}
+
+#[post("/amend-inspection")]
+async fn amend_inspection(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: AmendInspection = serde_json::de::from_str({
         let mut bytes = web::BytesMut::new();
         while let Some(item) = payload.next().await {
             bytes.extend_from_slice(&item?);
@@ -290,25 +537,62 @@ async fn user_index(mut payload: web::Payload) -> Result<HttpResponse> {
             .as_str()
     })?;
 
-    match request.check_token_validy() {
-        TokenResponse::Valid => {
-            Ok(HttpResponse::Ok().body(serde_json::ser::to_string(&read_user_index()?)?))
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    if request.reason.trim().is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("A reason is required"));
+    }
+
+    request
+        .amended
+        .validate(&data::load_deficiency_codes(&tenant_of(&request.token)?)?)
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    let mut user = data::User::read_for_tenant(request.user_uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+    let inspection = user
+        .find_inspection_mut(&request.inspection_id)
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Inspection not found"))?;
+
+    check_can_change_inspection(&request.token, inspection)?;
+    if let Some(id) = &inspection.event {
+        let event = data::find_event(id, &tenant_of(&request.token)?)
+            .map_err(|_| actix_web::error::ErrorNotFound("Event not found"))?;
+        if request.amended.name != event.template {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Inspection does not use the event's template",
+            ));
         }
-        TokenResponse::Invalid => Err(actix_web::error::ErrorForbidden("Invalid Token")),
-        TokenResponse::Expired => Err(actix_web::error::ErrorForbidden("Expired Token")),
     }
+
+    inspection
+        .amend(
+            request.amended,
+            request.token.user_uuid().to_string(),
+            request.reason,
+        )
+        .map_err(actix_web::error::ErrorConflict)?;
+    user.push_to_data_base();
+
+    Ok(HttpResponse::Ok().finish())
 }
 
-#[derive(Deserialize, Clone)]
-struct BulkUserRequest {
+#[derive(Deserialize)]
+struct VoidInspection {
     token: Token,
-    flight: Option<Flight>,
-    names: Vec<Option<String>>,
+    user_uuid: String,
+    inspection_id: String,
+    reason: String,
 }
 
-#[post("/bulk-new-user")]
-async fn bulk_new_user(mut payload: web::Payload) -> Result<HttpResponse> {
-    let request: BulkUserRequest = serde_json::de::from_str({
+#[post("/void-inspection")]
+async fn void_inspection(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: VoidInspection = serde_json::de::from_str({
         let mut bytes = web::BytesMut::new();
         while let Some(item) = payload.next().await {
             bytes.extend_from_slice(&item?);
@@ -318,54 +602,2023 @@ async fn bulk_new_user(mut payload: web::Payload) -> Result<HttpResponse> {
             .as_str()
     })?;
 
-    // Check if the token is valid, early return if it is not
-    let _ = match request.token.check_token_validy() {
+    match request.token.check_token_validy() {
         TokenResponse::Expired | TokenResponse::Invalid => {
             Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
         }
         TokenResponse::Valid => Ok(()),
     }?;
 
-    // Generate new users
-    let new_users: Vec<data::FlightIndexItem> = request
-        .names
-        .iter()
-        .map(|n| {
-            let mut new_user = data::User::new();
-            new_user.username = n.clone();
-            new_user.flight = (&request.flight).clone();
-            new_user.push_to_data_base();
-            new_user.dev_user = true;
-            new_user.into()
-        })
-        .collect();
+    if request.reason.trim().is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("A reason is required"));
+    }
 
-    Ok(HttpResponse::Ok().body(serde_json::ser::to_string(&new_users)?))
-}
+    let mut user = data::User::read_for_tenant(request.user_uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+    let inspection = user
+        .find_inspection_mut(&request.inspection_id)
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Inspection not found"))?;
 
-#[get("/flight_list")]
-async fn serve_flight_list() -> HttpResponse {
-    let flights: [String; 5] = [
-        "Beddoe".into(),
-        "Morgan".into(),
-        "Spear".into(),
-        "Bell".into(),
-        "Hill".into(),
-    ];
-    HttpResponse::Found().body(serde_json::to_string(&flights).unwrap())
-}
+    check_can_change_inspection(&request.token, inspection)?;
+
+    inspection
+        .void(request.token.user_uuid().to_string(), request.reason)
+        .map_err(actix_web::error::ErrorConflict)?;
+    user.push_to_data_base();
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+struct RankQuery {
+    rank: Option<String>,
+}
+
+#[get("/inspections.json")]
+async fn return_inspections(
+    query: web::Query<TenantQuery>,
+    rank: web::Query<RankQuery>,
+) -> Result<HttpResponse> {
+    let mut inspections = data::load_inspection_list(&query.tenant()?, rank.rank.as_deref())
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Internal Error Occured"))?;
+
+    inspections.iter_mut().for_each(|f| f.compute_score());
+
+    Ok(HttpResponse::Found()
+        .body(serde_json::ser::to_string(&inspections).expect("This should always work")))
+}
+
+#[get("/deficiency_codes.json")]
+async fn return_deficiency_codes(query: web::Query<TenantQuery>) -> Result<HttpResponse> {
+    let codes = data::load_deficiency_codes(&query.tenant()?)
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Internal Error Occured"))?;
+
+    Ok(HttpResponse::Ok().body(serde_json::ser::to_string(&codes)?))
+}
+
+#[derive(Deserialize)]
+struct SetDeficiencyCodes {
+    token: Token,
+    codes: Vec<data::DeficiencyCode>,
+}
+
+#[post("/deficiency_codes")]
+async fn set_deficiency_codes(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: SetDeficiencyCodes = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    data::write_deficiency_codes(&tenant_of(&request.token)?, request.codes)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/deficiency_report")]
+async fn deficiency_report(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: Token = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.check_token_validy() {
+        TokenResponse::Valid => Ok(HttpResponse::Ok().body(serde_json::ser::to_string(
+            &data::count_deficiencies(&tenant_of(&request)?)?,
+        )?)),
+        TokenResponse::Invalid => Err(actix_web::error::ErrorForbidden("Invalid Token")),
+        TokenResponse::Expired => Err(actix_web::error::ErrorForbidden("Expired Token")),
+    }
+}
+
+/// Accepts a `multipart/form-data` upload with the fields `token`, `user_uuid`, `inspection_id`,
+/// an optional `category_name` and the image itself as `file`. `token` must come before `file`
+#[post("/attachments/upload")]
+async fn upload_attachment(mut payload: Multipart) -> Result<HttpResponse> {
+    let mut token: Option<Token> = None;
+    let mut user_uuid: Option<String> = None;
+    let mut inspection_id: Option<String> = None;
+    let mut category_name: Option<String> = None;
+    let mut file: Option<(String, web::BytesMut)> = None;
+
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let name = field.name().unwrap_or_default().to_string();
+        let content_type = field.content_type().map(|f| f.essence_str().to_string());
+
+        // The token is checked as soon as it arrives, so the image is never read for a request
+        // that is not allowed to upload
+        if name == "file" && token.is_none() {
+            return Err(actix_web::error::ErrorForbidden(
+                "Token must be sent before the file",
+            ));
+        }
+
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = field.next().await {
+            let item = item?;
+            if bytes.len() + item.len() > attachments::MAX_SIZE {
+                return Err(actix_web::error::ErrorPayloadTooLarge(
+                    "Attachment too large",
+                ));
+            }
+            bytes.extend_from_slice(&item);
+        }
+
+        let text = || {
+            String::from_utf8(bytes.to_vec())
+                .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))
+        };
+
+        match name.as_str() {
+            "token" => {
+                let t: Token = serde_json::de::from_str(text()?.as_str())?;
+                match t.check_token_validy() {
+                    TokenResponse::Expired | TokenResponse::Invalid => {
+                        return Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+                    }
+                    TokenResponse::Valid => token = Some(t),
+                }
+            }
+            "user_uuid" => user_uuid = Some(text()?),
+            "inspection_id" => inspection_id = Some(text()?),
+            "category_name" => category_name = Some(text()?),
+            "file" => file = content_type.map(|t| (t, bytes)),
+            _ => (),
+        }
+    }
+
+    let (Some(token), Some(user_uuid), Some(inspection_id), Some((content_type, bytes))) =
+        (token, user_uuid, inspection_id, file)
+    else {
+        return Err(actix_web::error::ErrorBadRequest("Missing field"));
+    };
+
+    if !attachments::is_supported(&content_type) {
+        return Err(actix_web::error::ErrorUnsupportedMediaType(
+            "Only JPEG and PNG images are supported",
+        ));
+    }
+
+    let mut user = data::User::read_for_tenant(user_uuid, &tenant_of(&token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+    // Stored under the uuid the cadet was resolved to, a merged duplicate's uuid is only a
+    // redirect
+    let user_uuid = user.uuid.clone();
+    let inspection = user
+        .find_inspection_mut(&inspection_id)
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Inspection not found"))?;
+
+    let attachment_id = attachments::store(&user_uuid, &content_type, &bytes)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Could not read image"))?;
+
+    inspection.attachments.push(data::Attachment {
+        id: attachment_id.clone(),
+        category_name,
+        content_type,
+        uploaded_by: token.user_uuid().to_string(),
+        uploaded_at: chrono::Utc::now().timestamp(),
+    });
+    user.push_to_data_base();
+
+    Ok(HttpResponse::Ok().body(attachment_id))
+}
+
+#[derive(Deserialize)]
+struct AttachmentRequest {
+    token: Token,
+    user_uuid: String,
+    attachment_id: String,
+    #[serde(default)]
+    thumbnail: bool,
+}
+
+#[post("/attachment")]
+async fn get_attachment(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: AttachmentRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let user = data::User::read_for_tenant(request.user_uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+    let attachment = user
+        .inspections
+        .iter()
+        .flat_map(|f| f.attachments.iter())
+        .find(|f| f.id == request.attachment_id)
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Attachment not found"))?;
+
+    let bytes = attachments::read(
+        &user.uuid,
+        &attachment.id,
+        &attachment.content_type,
+        request.thumbnail,
+    )
+    .map_err(|_| actix_web::error::ErrorNotFound("Attachment not found"))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(match request.thumbnail {
+            true => "image/jpeg",
+            false => attachment.content_type.as_str(),
+        })
+        .body(bytes))
+}
+
+use auth::database::{self as auth_database, TokenResponse};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct UserLogin {
+    username: String,
+    password: String,
+}
+
+#[post("/auth/login")]
+async fn login(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: UserLogin = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    Ok(
+        match auth_database::User::get_user(request.username, request.password) {
+            None => HttpResponse::NotFound().finish(),
+            Some(t) if !t.approved => HttpResponse::Forbidden().body("Waiting for admin approval"),
+            Some(mut t) => {
+                t.accosiate_token();
+                t.clone().push_to_disk();
+                HttpResponse::Found().body(
+                    serde_json::to_string(
+                        t.tokens
+                            .last()
+                            .expect("We just added a token so we should be good here"),
+                    )
+                    .unwrap(),
+                )
+            }
+        },
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct UserSignup {
+    username: String,
+    password: String,
+    #[serde(default = "data::default_tenant")]
+    tenant: String,
+}
+
+#[post("/auth/signup")]
+async fn signup(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: UserSignup = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    data::find_tenant(&request.tenant)
+        .map_err(|_| actix_web::error::ErrorNotFound("Tenant not found"))?;
+
+    let user = auth_database::User::new(request.username, request.password, request.tenant)
+        .map_err(|_| actix_web::error::ErrorLocked("Username taken"))?;
+
+    user.push_to_disk();
+
+    Ok(HttpResponse::Accepted().body("Waiting for admin approval"))
+}
+
+#[derive(Serialize)]
+struct PendingAccount {
+    uuid: String,
+    username: String,
+}
+
+/// Accounts in the admin's tenant that signed up and are waiting for approval
+#[post("/auth/pending")]
+async fn pending_accounts(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: Token = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request)?;
+
+    let pending: Vec<PendingAccount> = auth_database::User::pending(&tenant_of(&request)?)
+        .iter()
+        .map(|f| PendingAccount {
+            uuid: f.uuid().to_string(),
+            username: f.username().to_string(),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&pending)?))
+}
+
+#[derive(Deserialize)]
+struct ApproveAccount {
+    token: Token,
+    uuid: String,
+}
+
+/// Lets an account in the admin's tenant log in
+#[post("/auth/approve")]
+async fn approve_account(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: ApproveAccount = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    let tenant = tenant_of(&request.token)?;
+    let mut account = auth_database::User::find(&request.uuid)
+        .filter(|f| f.tenant == tenant)
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Account not found"))?;
+
+    account.approved = true;
+    account.push_to_disk();
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+struct UserClaim {
+    /// As read from the QR code
+    uuid: String,
+    username: String,
+    /// The code printed with the QR card, without it the claim waits for staff approval
+    code: Option<String>,
+}
+
+#[post("/claim-user")]
+async fn claim_user(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: UserClaim = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+    let mut user = cadet_from_card(&request.uuid)?;
+
+    if user.username.is_some() {
+        return Err(actix_web::error::ErrorForbidden(
+            "Forbiden username already set",
+        ));
+    }
+
+    match request.code {
+        Some(_) if user.claim_attempts >= data::MAX_CLAIM_ATTEMPTS => {
+            Err(actix_web::error::ErrorTooManyRequests(
+                "Too many incorrect codes, ask staff for a new one",
+            ))
+        }
+        Some(code) => match user.claim_code.as_deref() == Some(code.trim().to_uppercase().as_str())
+        {
+            true => {
+                user.username = Some(request.username);
+                user.claim_code = None;
+                user.claim_attempts = 0;
+                user.pending_claim = None;
+                user.push_to_data_base();
+                Ok(HttpResponse::Ok().finish())
+            }
+            false => {
+                user.claim_attempts += 1;
+                user.push_to_data_base();
+                Err(actix_web::error::ErrorForbidden("Incorrect claim code"))
+            }
+        },
+        // Otherwise anyone holding the card could swap their own name in before staff approve
+        None if user.pending_claim.is_some() => Err(actix_web::error::ErrorConflict(
+            "A claim is already waiting for staff approval",
+        )),
+        None => {
+            user.pending_claim = Some(request.username);
+            user.push_to_data_base();
+            Ok(HttpResponse::Accepted().body("Waiting for staff approval"))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ApproveClaim {
+    token: Token,
+    uuid: String,
+    /// The username staff checked with the cadet, it must match the one waiting for approval
+    username: String,
+}
+
+/// Accepts a claim that was made without a code
+#[post("/approve-claim")]
+async fn approve_claim(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: ApproveClaim = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    let mut user = data::User::read_for_tenant(request.uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    match user.pending_claim.as_deref() {
+        Some(username) if user.username.is_none() && username == request.username => {
+            user.username = user.pending_claim.take();
+            user.claim_code = None;
+            user.push_to_data_base();
+            Ok(HttpResponse::Ok().finish())
+        }
+        Some(_) if user.username.is_none() => Err(actix_web::error::ErrorConflict(
+            "The claim waiting for approval is for a different username",
+        )),
+        _ => Err(actix_web::error::ErrorNotFound(
+            "No claim waiting for approval",
+        )),
+    }
+}
+
+/// Clears a wrong claim and issues a new claim code, which is returned. Also unlocks a code that
+/// had too many incorrect attempts
+#[post("/reset-claim")]
+async fn reset_claim(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: CadetRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    let mut user = data::User::read_for_tenant(request.uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    let code = user.issue_claim_code();
+    user.username = None;
+    user.pending_claim = None;
+    user.push_to_data_base();
+
+    Ok(HttpResponse::Ok().body(code))
+}
+
+#[actix_web::get("/{url:.*}")]
+async fn http_upgrade(req: HttpRequest) -> Result<HttpResponse> {
+    let file_string = fs::read_to_string("../UUIS-backend/https_upgrade.html")
+        .expect("https upgrade screen missing")
+        .replace("\n", "");
+
+    // Send each squadron back to its own site
+    let host = req.connection_info().host().to_string();
+    let redirect = data::load_tenants()?
+        .into_iter()
+        .map(|f| f.base_url)
+        .find(|f| f.trim_start_matches("https://") == host)
+        .unwrap_or_else(|| "https://uniform.952aircadets.ca".into());
+
+    Ok(HttpResponse::Found()
+        .content_type("text/html")
+        .insert_header(("redirect", redirect))
+        .body(file_string))
+}
+
+/// Checks that a cadet can be assigned to the unit, cadets belong to flights or sections
+fn check_assignable_unit(id: &str, tenant: &str) -> Result<()> {
+    match data::load_tenant_units(tenant)?.iter().find(|f| f.id == id) {
+        Some(t) if t.kind != data::UnitKind::Squadron => Ok(()),
+        Some(_) => Err(actix_web::error::ErrorBadRequest(
+            "Cadets must be assigned to a flight or section",
+        )),
+        None => Err(actix_web::error::ErrorNotFound("Flight not found")),
+    }
+}
+
+#[derive(Deserialize)]
+struct SetFlight {
+    uuid: String,
+    flight: String,
+}
+
+#[post("/set_flight")]
+async fn set_flight(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: SetFlight = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    let mut user = data::User::read_from_database(request.uuid)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    check_assignable_unit(&request.flight, &user.tenant)?;
+
+    user.flight = Some(request.flight);
+    user.push_to_data_base();
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+struct UpdateProfile {
+    token: Token,
+    uuid: String,
+    profile: data::Profile,
+}
+
+/// Replaces a cadet's profile, the rank can only be changed through `/promote`
+#[post("/update-profile")]
+async fn update_profile(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: UpdateProfile = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let mut user = data::User::read_for_tenant(request.uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    if request.profile.rank != user.profile.rank {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Rank can only be changed with a promotion",
+        ));
+    }
+
+    user.profile = request.profile;
+    user.push_to_data_base();
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&data::FlightIndexItem::from(&user))?))
+}
+
+#[derive(Deserialize)]
+struct CadetRequest {
+    token: Token,
+    uuid: String,
+}
+
+#[derive(Clone, Copy)]
+enum CadetAction {
+    Archive,
+    Deactivate,
+    Restore,
+}
+
+async fn change_cadet_state(
+    mut payload: web::Payload,
+    action: CadetAction,
+) -> Result<HttpResponse> {
+    let request: CadetRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    let mut user = data::User::read_for_tenant(request.uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    match action {
+        CadetAction::Archive => user.archived = Some(chrono::Utc::now().timestamp()),
+        CadetAction::Deactivate => user.profile.status = data::CadetStatus::Inactive,
+        CadetAction::Restore => {
+            user.archived = None;
+            user.profile.status = data::CadetStatus::Active;
+        }
+    }
+    user.push_to_data_base();
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/archive-cadet")]
+async fn archive_cadet(payload: web::Payload) -> Result<HttpResponse> {
+    change_cadet_state(payload, CadetAction::Archive).await
+}
+
+#[post("/deactivate-cadet")]
+async fn deactivate_cadet(payload: web::Payload) -> Result<HttpResponse> {
+    change_cadet_state(payload, CadetAction::Deactivate).await
+}
+
+#[post("/restore-cadet")]
+async fn restore_cadet(payload: web::Payload) -> Result<HttpResponse> {
+    change_cadet_state(payload, CadetAction::Restore).await
+}
+
+/// Permanently deletes a cadet, their inspection history and photos, for privacy requests
+#[post("/delete-cadet")]
+async fn delete_cadet(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: CadetRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    let user = data::User::read_for_tenant(request.uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    user.delete_from_database()?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+struct MergeCadets {
+    token: Token,
+    /// The record that is kept
+    keep: String,
+    /// The duplicate, it becomes a redirect to `keep`
+    merge: String,
+}
+
+#[post("/merge-cadets")]
+async fn merge_cadets(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: MergeCadets = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+    let tenant = tenant_of(&request.token)?;
+
+    let mut keep = data::User::read_for_tenant(request.keep, &tenant)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+    let mut merge = data::User::read_for_tenant(request.merge, &tenant)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    // Either uuid may already redirect, compare the records they resolve to
+    if keep.uuid == merge.uuid {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Cannot merge a cadet into itself",
+        ));
+    }
+
+    keep.merge(&mut merge)?;
+    merge.push_to_data_base();
+    keep.push_to_data_base();
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&data::FlightIndexItem::from(&keep))?))
+}
+
+fn check_rank(rank: &str, tenant: &str) -> Result<()> {
+    match data::load_ranks(tenant)?.iter().any(|f| f.id == rank) {
+        true => Ok(()),
+        false => Err(actix_web::error::ErrorBadRequest("Rank not found")),
+    }
+}
+
+#[get("/ranks")]
+async fn serve_ranks(query: web::Query<TenantQuery>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&data::load_ranks(&query.tenant()?)?)?))
+}
+
+#[derive(Deserialize)]
+struct SetRanks {
+    token: Token,
+    ranks: Vec<data::Rank>,
+}
+
+/// Replaces the admin's tenant's rank ladder
+#[post("/ranks")]
+async fn set_ranks(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: SetRanks = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    data::write_ranks(&tenant_of(&request.token)?, request.ranks)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+struct PromoteCadet {
+    token: Token,
+    uuid: String,
+    rank: String,
+    /// Defaults to now
+    date: Option<i64>,
+}
+
+/// Records a promotion approved by the admin making the request
+#[post("/promote")]
+async fn promote_cadet(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: PromoteCadet = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    let tenant = tenant_of(&request.token)?;
+    check_rank(&request.rank, &tenant)?;
+
+    let mut user = data::User::read_for_tenant(request.uuid, &tenant)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    user.promote(
+        request.rank,
+        request
+            .date
+            .unwrap_or_else(|| chrono::Utc::now().timestamp()),
+        request.token.user_uuid().to_string(),
+    );
+    user.push_to_data_base();
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&user.promotions)?))
+}
+
+#[post("/user_index")]
+async fn user_index(
+    mut payload: web::Payload,
+    filter: web::Query<data::IndexFilter>,
+) -> Result<HttpResponse> {
+    let request: Token = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.check_token_validy() {
+        TokenResponse::Valid => {
+            let page = filter
+                .apply(read_user_index(&tenant_of(&request)?)?)
+                .map_err(actix_web::error::ErrorBadRequest)?;
+
+            // Without a page size the whole index is returned as before
+            match filter.limit {
+                Some(_) => Ok(HttpResponse::Ok().body(serde_json::ser::to_string(&page)?)),
+                None => Ok(HttpResponse::Ok().body(serde_json::ser::to_string(&page.items)?)),
+            }
+        }
+        TokenResponse::Invalid => Err(actix_web::error::ErrorForbidden("Invalid Token")),
+        TokenResponse::Expired => Err(actix_web::error::ErrorForbidden("Expired Token")),
+    }
+}
+
+#[derive(Deserialize, Clone)]
+struct BulkUserRequest {
+    token: Token,
+    flight: Option<String>,
+    names: Vec<Option<String>>,
+}
+
+#[derive(Serialize)]
+struct NewCadet {
+    #[serde(flatten)]
+    cadet: data::FlightIndexItem,
+    #[serde(flatten)]
+    card: CardCode,
+}
+
+#[post("/bulk-new-user")]
+async fn bulk_new_user(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: BulkUserRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    // Check if the token is valid, early return if it is not
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let tenant = tenant_of(&request.token)?;
+    if let Some(flight) = &request.flight {
+        check_assignable_unit(flight, &tenant)?;
+    }
+
+    // Generate new users
+    let new_users: Vec<NewCadet> = request
+        .names
+        .iter()
+        .map(|n| {
+            let mut new_user = data::User::new(tenant.clone());
+            new_user.username = n.clone();
+            new_user.flight = request.flight.clone();
+            new_user.push_to_data_base();
+            new_user.dev_user = true;
+            Ok(NewCadet {
+                card: CardCode::new(&new_user)?,
+                cadet: new_user.into(),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(HttpResponse::Ok().body(serde_json::ser::to_string(&new_users)?))
+}
+
+#[get("/flight_list")]
+async fn serve_flight_list(query: web::Query<TenantQuery>) -> Result<HttpResponse> {
+    let flights: Vec<String> = data::load_tenant_units(&query.tenant()?)?
+        .into_iter()
+        .filter(|f| f.kind == data::UnitKind::Flight)
+        .map(|f| f.id)
+        .collect();
+
+    Ok(HttpResponse::Found().body(serde_json::to_string(&flights)?))
+}
+
+#[get("/units")]
+async fn serve_units(query: web::Query<TenantQuery>) -> Result<HttpResponse> {
+    Ok(
+        HttpResponse::Ok().body(serde_json::to_string(&data::load_tenant_units(
+            &query.tenant()?,
+        )?)?),
+    )
+}
+
+/// Checks the token is valid and belongs to an admin
+fn check_admin(token: &Token) -> Result<()> {
+    match token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => match token.role() {
+            Some(auth_database::Role::Admin) => Ok(()),
+            _ => Err(actix_web::error::ErrorForbidden("Admin access required")),
+        },
+    }
+}
+
+#[derive(Deserialize)]
+struct SaveUnit {
+    token: Token,
+    unit: data::Unit,
+}
+
+/// Creates a unit, or replaces the unit with the same id
+#[post("/units/save")]
+async fn save_unit(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: SaveUnit = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+    let tenant = tenant_of(&request.token)?;
+
+    let mut units = data::load_units()?;
+    let mut unit = request.unit;
+    unit.tenant = tenant.clone();
+    if unit.id.is_empty() {
+        unit.id = uuid::Uuid::new_v4().to_string();
+    }
+    if units.iter().any(|f| f.id == unit.id && f.tenant != tenant) {
+        return Err(actix_web::error::ErrorConflict("Unit id already in use"));
+    }
+    if unit.parent.as_ref() == Some(&unit.id) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "A unit cannot be its own parent",
+        ));
+    }
+    unit.validate_parent(&data::load_tenant_units(&tenant)?)
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    // Child units and cadets were placed for the old kind, a flight turned into a section would
+    // leave its sections and its cadets' flight roll-up pointing at the wrong level
+    let kind_changed = units.iter().any(|f| f.id == unit.id && f.kind != unit.kind);
+    if kind_changed && units.iter().any(|f| f.parent.as_ref() == Some(&unit.id)) {
+        return Err(actix_web::error::ErrorConflict(
+            "Move the unit's child units before changing its kind",
+        ));
+    }
+    if kind_changed
+        && data::read_tenant_users(&tenant)?
+            .iter()
+            .any(|f| f.flight.as_ref() == Some(&unit.id))
+    {
+        return Err(actix_web::error::ErrorConflict(
+            "Move the unit's cadets before changing its kind",
+        ));
+    }
+
+    match units.iter().position(|f| f.id == unit.id) {
+        None => units.push(unit.clone()),
+        Some(t) => units[t] = unit.clone(),
+    };
+    data::write_units(&units)?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&unit)?))
+}
+
+#[derive(Deserialize)]
+struct DeleteUnit {
+    token: Token,
+    id: String,
+}
+
+/// Deletes a unit that has no child units and no cadets assigned to it
+#[post("/units/delete")]
+async fn delete_unit(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: DeleteUnit = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+    let tenant = tenant_of(&request.token)?;
+
+    let mut units = data::load_units()?;
+    if !units
+        .iter()
+        .any(|f| f.id == request.id && f.tenant == tenant)
+    {
+        return Err(actix_web::error::ErrorNotFound("Unit not found"));
+    }
+    if units.iter().any(|f| f.parent.as_ref() == Some(&request.id)) {
+        return Err(actix_web::error::ErrorConflict(
+            "Unit still has child units",
+        ));
+    }
+    if data::read_tenant_users(&tenant)?
+        .iter()
+        .any(|f| f.flight.as_ref() == Some(&request.id))
+    {
+        return Err(actix_web::error::ErrorConflict("Unit still has cadets"));
+    }
+
+    units.retain(|f| f.id != request.id);
+    data::write_units(&units)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Inspections may only be recorded against an open event of the same tenant, using the event's
+/// template and for a cadet in the units it covers
+fn check_event_open(inspection: &data::Inspection, cadet: &data::User, tenant: &str) -> Result<()> {
+    if let Some(id) = &inspection.event {
+        let event = open_event(id, tenant)?;
+        if inspection.name != event.template {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Inspection does not use the event's template",
+            ));
+        }
+        if !event.includes(cadet, &data::load_tenant_units(tenant)?) {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Cadet is not in a unit covered by the event",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct CreateEvent {
+    token: Token,
+    name: String,
+    date: i64,
+    template: String,
+    flights: Vec<String>,
+}
+
+#[post("/events/create")]
+async fn create_event(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: CreateEvent = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+    let tenant = tenant_of(&request.token)?;
+
+    if !data::load_inspection_list(&tenant, None)?
+        .iter()
+        .any(|f| f.name == request.template)
+    {
+        return Err(actix_web::error::ErrorBadRequest("Unknown template"));
+    }
+    if request.flights.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "An event needs at least one flight",
+        ));
+    }
+    let units = data::load_tenant_units(&tenant)?;
+    if let Some(t) = request
+        .flights
+        .iter()
+        .find(|f| !units.iter().any(|u| &u.id == *f))
+    {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Unknown unit: {}",
+            t
+        )));
+    }
+
+    let event = data::Event {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: request.name,
+        date: request.date,
+        template: request.template,
+        flights: request.flights,
+        status: data::EventStatus::Open,
+        closed_at: None,
+        tenant,
+        assignments: vec![],
+    };
+    event.save()?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&event)?))
+}
+
+#[post("/events")]
+async fn list_events(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: Token = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.check_token_validy() {
+        TokenResponse::Valid => Ok(HttpResponse::Ok().body(serde_json::to_string(
+            &data::load_events(&tenant_of(&request)?)?,
+        )?)),
+        TokenResponse::Invalid => Err(actix_web::error::ErrorForbidden("Invalid Token")),
+        TokenResponse::Expired => Err(actix_web::error::ErrorForbidden("Expired Token")),
+    }
+}
+
+/// Inspection results grouped by event
+#[post("/events/report")]
+async fn event_report(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: Token = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.check_token_validy() {
+        TokenResponse::Valid => Ok(HttpResponse::Ok().body(serde_json::to_string(
+            &data::summarize_events(&tenant_of(&request)?)?,
+        )?)),
+        TokenResponse::Invalid => Err(actix_web::error::ErrorForbidden("Invalid Token")),
+        TokenResponse::Expired => Err(actix_web::error::ErrorForbidden("Expired Token")),
+    }
+}
+
+#[derive(Deserialize)]
+struct EventRequest {
+    token: Token,
+    id: String,
+}
+
+/// Which cadets have and have not been inspected during an event, with their results
+#[post("/events/status")]
+async fn event_status(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: EventRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let event = data::find_event(&request.id, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("Event not found"))?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&data::EventReport::new(event)?)?))
+}
+
+/// Closes an event so no further inspections can be recorded against it
+#[post("/events/close")]
+async fn close_event(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: EventRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    let mut event = data::find_event(&request.id, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("Event not found"))?;
+    if event.status == data::EventStatus::Closed {
+        return Err(actix_web::error::ErrorConflict("Event is already closed"));
+    }
+    event.status = data::EventStatus::Closed;
+    event.closed_at = Some(chrono::Utc::now().timestamp());
+    event.save()?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&data::EventReport::new(event)?)?))
+}
+
+#[derive(Deserialize)]
+struct AssignInspectors {
+    token: Token,
+    id: String,
+    assignments: Vec<data::Assignment>,
+}
+
+/// Replaces who inspects which flights or cadets during an event
+#[post("/events/assign")]
+async fn assign_inspectors(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: AssignInspectors = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+    let tenant = tenant_of(&request.token)?;
+    let mut event = open_event(&request.id, &tenant)?;
+
+    let units = data::load_tenant_units(&tenant)?;
+    for assignment in request.assignments.iter() {
+        if auth_database::User::find(&assignment.inspector).is_none_or(|f| f.tenant != tenant) {
+            return Err(actix_web::error::ErrorBadRequest(format!(
+                "Unknown inspector: {}",
+                assignment.inspector
+            )));
+        }
+        if let Some(t) = assignment
+            .flights
+            .iter()
+            .find(|f| !units.iter().any(|u| &u.id == *f))
+        {
+            return Err(actix_web::error::ErrorBadRequest(format!(
+                "Unknown unit: {}",
+                t
+            )));
+        }
+        for cadet in assignment.cadets.iter() {
+            data::User::read_for_tenant(cadet.clone(), &tenant)
+                .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+        }
+    }
+
+    event.assignments = request.assignments;
+    event.save()?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&event)?))
+}
+
+/// The cadets still waiting to be inspected by the caller during an event
+#[post("/events/my-queue")]
+async fn my_queue(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: EventRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let event = data::find_event(&request.id, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("Event not found"))?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(
+        &event.queue(request.token.user_uuid())?,
+    )?))
+}
+
+#[derive(Deserialize)]
+struct RebalanceEvent {
+    token: Token,
+    id: String,
+    /// Uuids of the inspectors that are not at the event
+    absent: Vec<String>,
+}
+
+#[post("/events/rebalance")]
+async fn rebalance_event(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: RebalanceEvent = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+    let mut event = open_event(&request.id, &tenant_of(&request.token)?)?;
+
+    if event
+        .assignments
+        .iter()
+        .all(|f| request.absent.contains(&f.inspector))
+    {
+        return Err(actix_web::error::ErrorConflict(
+            "No inspectors left to assign to",
+        ));
+    }
+    event.rebalance(&request.absent)?;
+    event.save()?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&event)?))
+}
+
+/// An event that can still be changed, closed events are read only
+fn open_event(id: &str, tenant: &str) -> Result<data::Event> {
+    match data::find_event(id, tenant) {
+        Err(_) => Err(actix_web::error::ErrorNotFound("Event not found")),
+        Ok(t) if t.status == data::EventStatus::Closed => {
+            Err(actix_web::error::ErrorConflict("Event is closed"))
+        }
+        Ok(t) => Ok(t),
+    }
+}
+
+#[derive(Deserialize)]
+struct AttendanceMark {
+    user_uuid: String,
+    status: data::AttendanceStatus,
+}
+
+#[derive(Deserialize)]
+struct MarkAttendance {
+    token: Token,
+    event: String,
+    records: Vec<AttendanceMark>,
+}
+
+#[post("/attendance/mark")]
+async fn mark_attendance(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: MarkAttendance = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let tenant = tenant_of(&request.token)?;
+    let event = open_event(&request.event, &tenant)?;
+
+    // Read every cadet first so nothing is written if one of them is missing
+    let mut cadets = vec![];
+    for record in request.records {
+        let cadet = data::User::read_for_tenant(record.user_uuid, &tenant)
+            .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+        cadets.push((cadet, record.status));
+    }
+
+    for (mut cadet, status) in cadets {
+        cadet.mark_attendance(&event.id, status, request.token.user_uuid());
+        cadet.write_to_disk()?;
+    }
+    index_users()?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&read_user_index(&tenant)?)?))
+}
+
+#[derive(Deserialize)]
+struct MarkFlightAttendance {
+    token: Token,
+    event: String,
+    flight: String,
+    /// Applied to every active cadet in the flight, including its sections
+    status: data::AttendanceStatus,
+    /// Cadets that should be marked differently from the rest of the flight
+    #[serde(default)]
+    exceptions: Vec<AttendanceMark>,
+}
+
+/// Marks a whole flight at once, e.g. everyone present except the listed exceptions
+#[post("/attendance/mark-flight")]
+async fn mark_flight_attendance(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: MarkFlightAttendance = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let tenant = tenant_of(&request.token)?;
+    let event = open_event(&request.event, &tenant)?;
+    let units = data::load_tenant_units(&tenant)?;
+    if !units.iter().any(|f| f.id == request.flight) {
+        return Err(actix_web::error::ErrorNotFound("Unit not found"));
+    }
+
+    let cadets = data::read_tenant_users(&tenant)?.into_iter().filter(|f| {
+        f.archived.is_none()
+            && f.profile.status == data::CadetStatus::Active
+            && f.flight
+                .as_ref()
+                .is_some_and(|t| data::unit_within(&units, t, &request.flight))
+    });
+
+    for mut cadet in cadets {
+        let status = request
+            .exceptions
+            .iter()
+            .find(|f| f.user_uuid == cadet.uuid)
+            .map_or(request.status, |f| f.status);
+        cadet.mark_attendance(&event.id, status, request.token.user_uuid());
+        cadet.write_to_disk()?;
+    }
+    index_users()?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&read_user_index(&tenant)?)?))
+}
+
+#[derive(Deserialize)]
+struct CadetAnalytics {
+    token: Token,
+    user_uuid: String,
+    /// Number of inspections averaged over, defaults to 3
+    #[serde(default)]
+    window: Option<usize>,
+}
+
+/// Score history, moving average and most failed criteria for one cadet
+#[post("/analytics/cadet")]
+async fn cadet_analytics(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: CadetAnalytics = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let window = request.window.unwrap_or(3);
+    if window > analytics::MAX_WINDOW {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Window can be at most {}",
+            analytics::MAX_WINDOW
+        )));
+    }
+
+    let user = data::User::read_for_tenant(request.user_uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    Ok(
+        HttpResponse::Ok().body(serde_json::to_string(&analytics::CadetTrends::new(
+            &user, window,
+        ))?),
+    )
+}
+
+/// Scores per flight and for the whole squadron over time, along with overdue cadets
+#[post("/analytics/dashboard")]
+async fn dashboard(
+    mut payload: web::Payload,
+    query: web::Query<analytics::DashboardQuery>,
+) -> Result<HttpResponse> {
+    let request: Token = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.check_token_validy() {
+        TokenResponse::Valid => Ok(HttpResponse::Ok().body(serde_json::to_string(
+            &analytics::dashboard(&tenant_of(&request)?, &query)?,
+        )?)),
+        TokenResponse::Invalid => Err(actix_web::error::ErrorForbidden("Invalid Token")),
+        TokenResponse::Expired => Err(actix_web::error::ErrorForbidden("Expired Token")),
+    }
+}
+
+#[post("/competitions")]
+async fn list_competitions(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: Token = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.check_token_validy() {
+        TokenResponse::Valid => Ok(HttpResponse::Ok().body(serde_json::to_string(
+            &data::load_competitions(&tenant_of(&request)?)?,
+        )?)),
+        TokenResponse::Invalid => Err(actix_web::error::ErrorForbidden("Invalid Token")),
+        TokenResponse::Expired => Err(actix_web::error::ErrorForbidden("Expired Token")),
+    }
+}
+
+#[derive(Deserialize)]
+struct SaveCompetition {
+    token: Token,
+    competition: data::Competition,
+}
+
+/// Creates a competition period, or replaces the period with the same id
+#[post("/competitions/save")]
+async fn save_competition(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: SaveCompetition = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+    let tenant = tenant_of(&request.token)?;
+
+    let mut competition = request.competition;
+    if competition.start >= competition.end {
+        return Err(actix_web::error::ErrorBadRequest(
+            "A competition must end after it starts",
+        ));
+    }
+    if competition.id.is_empty() {
+        competition.id = uuid::Uuid::new_v4().to_string();
+        competition.results = None;
+    } else {
+        let existing = data::find_competition(&competition.id, &tenant)
+            .map_err(|_| actix_web::error::ErrorNotFound("Competition not found"))?;
+        // Saved results are only kept while they still describe the period
+        competition.results = match existing.same_period(&competition) {
+            true => existing.results,
+            false => None,
+        };
+    }
+    competition.tenant = tenant;
+    competition.save()?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&competition)?))
+}
+
+#[derive(Deserialize)]
+struct CompetitionRequest {
+    token: Token,
+    id: String,
+}
+
+/// Current standings of the flights in a competition period
+#[post("/competitions/standings")]
+async fn competition_standings(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: CompetitionRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let competition = data::find_competition(&request.id, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("Competition not found"))?;
+
+    Ok(
+        HttpResponse::Ok().body(serde_json::to_string(&analytics::Standings::new(
+            competition,
+        )?)?),
+    )
+}
+
+/// Winners of every finished competition period and the flights' overall record
+#[post("/competitions/leaderboard")]
+async fn competition_leaderboard(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: Token = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.check_token_validy() {
+        TokenResponse::Valid => Ok(HttpResponse::Ok().body(serde_json::to_string(
+            &analytics::Leaderboard::new(&tenant_of(&request)?)?,
+        )?)),
+        TokenResponse::Invalid => Err(actix_web::error::ErrorForbidden("Invalid Token")),
+        TokenResponse::Expired => Err(actix_web::error::ErrorForbidden("Expired Token")),
+    }
+}
+
+async fn export_table(
+    mut payload: web::Payload,
+    query: &export::ExportQuery,
+) -> Result<export::Table> {
+    let request: Token = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    Ok(export::Table::new(&tenant_of(&request)?, query)?)
+}
+
+#[post("/export/inspections.csv")]
+async fn export_csv(
+    payload: web::Payload,
+    query: web::Query<export::ExportQuery>,
+) -> Result<HttpResponse> {
+    let table = export_table(payload, &query).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header((
+            "Content-Disposition",
+            "attachment; filename=\"inspections.csv\"",
+        ))
+        .body(table.to_csv()?))
+}
+
+#[post("/export/inspections.xlsx")]
+async fn export_xlsx(
+    payload: web::Payload,
+    query: web::Query<export::ExportQuery>,
+) -> Result<HttpResponse> {
+    let table = export_table(payload, &query).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+        .insert_header((
+            "Content-Disposition",
+            "attachment; filename=\"inspections.xlsx\"",
+        ))
+        .body(table.to_xlsx()?))
+}
+
+#[derive(Deserialize)]
+struct CadetReport {
+    token: Token,
+    user_uuid: String,
+}
+
+#[post("/reports/cadet.pdf")]
+async fn cadet_report(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: CadetReport = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let tenant = tenant_of(&request.token)?;
+    let user = data::User::read_for_tenant(request.user_uuid, &tenant)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/pdf")
+        .body(reports::cadet_report(
+            &user,
+            &data::find_tenant(&tenant)?,
+            &data::load_tenant_units(&tenant)?,
+        )))
+}
+
+#[derive(Deserialize)]
+struct FlightReport {
+    token: Token,
+    flight: String,
+}
+
+#[post("/reports/flight.pdf")]
+async fn flight_report(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: FlightReport = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.token.check_token_validy() {
+        TokenResponse::Expired | TokenResponse::Invalid => {
+            Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+        }
+        TokenResponse::Valid => Ok(()),
+    }?;
+
+    let tenant = tenant_of(&request.token)?;
+    let units = data::load_tenant_units(&tenant)?;
+    let unit = units
+        .iter()
+        .find(|f| f.id == request.flight)
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Unit not found"))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/pdf")
+        .body(reports::flight_report(
+            unit,
+            &reports::unit_cadets(&tenant, &unit.id, &units)?,
+            &data::find_tenant(&tenant)?,
+            &units,
+        )))
+}
+
+#[derive(Deserialize)]
+struct CardSheet {
+    token: Token,
+    /// Cadets to print, in order
+    #[serde(default)]
+    cadets: Vec<String>,
+    /// Prints every active cadet in this unit after any listed cadets
+    flight: Option<String>,
+    #[serde(default)]
+    layout: cards::LayoutChoice,
+    #[serde(default)]
+    format: cards::Format,
+    /// Which page to render when the format is SVG
+    #[serde(default)]
+    page: usize,
+    /// Print each unclaimed cadet's claim code, only admins may ask for them
+    #[serde(default)]
+    claim_codes: bool,
+}
+
+/// A print ready sheet of wallet cards with each cadet's QR code, flight and optionally their claim
+/// code
+#[post("/cards")]
+async fn card_sheet(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: CardSheet = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    match request.claim_codes {
+        true => check_admin(&request.token)?,
+        false => match request.token.check_token_validy() {
+            TokenResponse::Expired | TokenResponse::Invalid => {
+                Err(actix_web::error::ErrorForbidden("Token Invalid or Expired"))
+            }
+            TokenResponse::Valid => Ok(()),
+        }?,
+    };
+
+    let layout = match request.layout {
+        cards::LayoutChoice::Preset(t) => cards::Layout::from(t),
+        cards::LayoutChoice::Custom(t) => t,
+    };
+    layout
+        .validate()
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    let tenant = tenant_of(&request.token)?;
+    let units = data::load_tenant_units(&tenant)?;
+
+    let mut cadets = vec![];
+    for uuid in request.cadets {
+        cadets.push(
+            data::User::read_for_tenant(uuid, &tenant)
+                .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?,
+        );
+    }
+    if let Some(flight) = &request.flight {
+        if !units.iter().any(|f| &f.id == flight) {
+            return Err(actix_web::error::ErrorNotFound("Unit not found"));
+        }
+        for cadet in reports::unit_cadets(&tenant, flight, &units)? {
+            if !cadets.iter().any(|f| f.uuid == cadet.uuid) {
+                cadets.push(cadet);
+            }
+        }
+    }
+    if cadets.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("No cadets to print"));
+    }
+
+    let tenant = data::find_tenant(&tenant)?;
+    let cards: Vec<cards::Card> = cadets
+        .iter()
+        .map(|f| {
+            let mut card = cards::Card::new(f, &tenant, &units);
+            if !request.claim_codes {
+                card.claim_code = None;
+            }
+            card
+        })
+        .collect();
+
+    match request.format {
+        cards::Format::Pdf => Ok(HttpResponse::Ok()
+            .content_type("application/pdf")
+            .body(cards::pdf_sheet(&cards, &layout, &tenant.name))),
+        cards::Format::Svg => {
+            let pages = cards.len().div_ceil(layout.per_page());
+            if request.page >= pages {
+                return Err(actix_web::error::ErrorNotFound("Page not found"));
+            }
+
+            Ok(HttpResponse::Ok()
+                .content_type("image/svg+xml")
+                .insert_header(("X-Page-Count", pages.to_string()))
+                .body(cards::svg_sheet(
+                    &cards,
+                    &layout,
+                    &tenant.name,
+                    request.page,
+                )))
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ReissuedCard {
+    #[serde(flatten)]
+    card: CardCode,
+    /// A fresh claim code when the cadet has not claimed their record yet
+    claim_code: Option<String>,
+}
+
+/// The code on a cadet's current card, for admins to print or show it again without revoking it
+#[post("/card-code")]
+async fn current_card_code(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: CadetRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    let user = data::User::read_for_tenant(request.uuid, &tenant_of(&request.token)?)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    Ok(HttpResponse::Ok().body(serde_json::to_string(&CardCode::new(&user)?)?))
+}
+
+/// Invalidates a lost card and returns the code to print on its replacement
+#[post("/revoke-card")]
+async fn revoke_card(mut payload: web::Payload) -> Result<HttpResponse> {
+    let request: CadetRequest = serde_json::de::from_str({
+        let mut bytes = web::BytesMut::new();
+        while let Some(item) = payload.next().await {
+            bytes.extend_from_slice(&item?);
+        }
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| actix_web::error::ErrorBadRequest("Could not parse request"))?
+            .as_str()
+    })?;
+
+    check_admin(&request.token)?;
+
+    let tenant = tenant_of(&request.token)?;
+    let mut user = data::revoke_card(&request.uuid, &tenant)
+        .map_err(|_| actix_web::error::ErrorNotFound("User not found"))?;
+
+    // The claim code was printed on the lost card too
+    if user.username.is_none() {
+        user.issue_claim_code();
+        user.pending_claim = None;
+    }
+    user.push_to_data_base();
+
+    Ok(
+        HttpResponse::Ok().body(serde_json::to_string(&ReissuedCard {
+            card: CardCode::new(&user)?,
+            claim_code: user.claim_code,
+        })?),
+    )
+}
 
 #[actix_web::main]
 async fn main() -> Result<(), std::io::Error> {
-    index_users()?;
     // initlize the .env file
     dotenv().ok();
+    env_logger::init_from_env(Env::default().default_filter_or("info"));
+
+    data::migrate_units()?;
+    data::migrate_inspection_ids()?;
+    data::migrate_claim_codes()?;
+    data::migrate_legacy_qr_windows()?;
+    data::init_card_key()?;
+    data::refresh_tenant_origins()?;
+    index_users()?;
 
     let private_key_path = env::var("PRIVKEY").unwrap();
     let cert_path = env::var("CERT").unwrap();
 
-    env_logger::init_from_env(Env::default().default_filter_or("info"));
-
     let mut ssl_builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
     ssl_builder
         .set_private_key_file(private_key_path, SslFiletype::PEM)
@@ -378,20 +2631,75 @@ async fn main() -> Result<(), std::io::Error> {
             .service(
                 scope("/api")
                     .service(get_user)
+                    .service(cadet_view)
                     .service(generate_user)
                     .service(return_inspections)
                     .service(add_inspection_to_user)
                     .service(return_inspections)
                     .service(signup)
+                    .service(pending_accounts)
+                    .service(approve_account)
                     .service(login)
                     .service(claim_user)
                     .service(validate_uuid)
                     .service(post_generate_user)
                     .service(get_qrcode_for_user)
+                    .service(get_qrcode_png_for_user)
                     .service(serve_flight_list)
+                    .service(serve_units)
+                    .service(save_unit)
+                    .service(delete_unit)
                     .service(set_flight)
                     .service(bulk_new_user)
-                    .service(user_index),
+                    .service(user_index)
+                    .service(return_deficiency_codes)
+                    .service(set_deficiency_codes)
+                    .service(deficiency_report)
+                    .service(upload_attachment)
+                    .service(get_attachment)
+                    .service(amend_inspection)
+                    .service(void_inspection)
+                    .service(save_draft)
+                    .service(list_drafts)
+                    .service(discard_draft)
+                    .service(finalize_draft)
+                    .service(serve_tenant)
+                    .service(save_tenant)
+                    .service(upload_tenant_logo)
+                    .service(update_profile)
+                    .service(serve_ranks)
+                    .service(set_ranks)
+                    .service(promote_cadet)
+                    .service(archive_cadet)
+                    .service(deactivate_cadet)
+                    .service(restore_cadet)
+                    .service(delete_cadet)
+                    .service(merge_cadets)
+                    .service(approve_claim)
+                    .service(reset_claim)
+                    .service(create_event)
+                    .service(list_events)
+                    .service(event_status)
+                    .service(event_report)
+                    .service(mark_attendance)
+                    .service(mark_flight_attendance)
+                    .service(assign_inspectors)
+                    .service(my_queue)
+                    .service(rebalance_event)
+                    .service(cadet_analytics)
+                    .service(dashboard)
+                    .service(list_competitions)
+                    .service(save_competition)
+                    .service(competition_standings)
+                    .service(competition_leaderboard)
+                    .service(export_csv)
+                    .service(export_xlsx)
+                    .service(cadet_report)
+                    .service(flight_report)
+                    .service(card_sheet)
+                    .service(revoke_card)
+                    .service(current_card_code)
+                    .service(close_event),
             )
             .service(
                 spa()
@@ -404,7 +2712,8 @@ async fn main() -> Result<(), std::io::Error> {
                 Cors::default()
                     .allowed_origin("https://uniform.952aircadets.ca")
                     .allowed_origin("https://beta.uniform.kapocsi.ca")
-                    .allowed_origin("http://127.0.0.1:5173"),
+                    .allowed_origin("http://127.0.0.1:5173")
+                    .allowed_origin_fn(|origin, _| data::is_tenant_origin(origin.as_bytes())),
             )
             .wrap(NormalizePath::trim())
     });
@@ -0,0 +1,1210 @@
+/// A minimal PDF writer, enough for text, lines and filled shapes using the built in Helvetica
+/// fonts so nothing has to be embedded
+pub mod pdf {
+    use std::fmt::Write;
+
+    /// US Letter in points
+    pub const LETTER: (f32, f32) = (612.0, 792.0);
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub enum Font {
+        Regular,
+        Bold,
+    }
+
+    /// Rough width of text in Helvetica, used to truncate and centre text
+    pub fn text_width(text: &str, size: f32) -> f32 {
+        text.chars().count() as f32 * size * 0.52
+    }
+
+    /// Shortens text to fit within `width`, ending it with "..." when cut
+    pub fn fit(text: &str, width: f32, size: f32) -> String {
+        if text_width(text, size) <= width {
+            return text.to_string();
+        }
+
+        let chars = ((width / (size * 0.52)) as usize).saturating_sub(3);
+        format!("{}...", text.chars().take(chars).collect::<String>())
+    }
+
+    /// Splits text into lines no wider than `width`, breaking between words
+    pub fn wrap(text: &str, width: f32, size: f32) -> Vec<String> {
+        let mut lines = vec![];
+        for paragraph in text.lines() {
+            let mut line = String::new();
+            for word in paragraph.split_whitespace() {
+                let candidate = match line.is_empty() {
+                    true => word.to_string(),
+                    false => format!("{line} {word}"),
+                };
+                if text_width(&candidate, size) > width && !line.is_empty() {
+                    lines.push(line);
+                    line = word.to_string();
+                } else {
+                    line = candidate;
+                }
+            }
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    /// Escapes text for a PDF string, characters outside Latin-1 are replaced with "?"
+    fn escape(text: &str) -> String {
+        let mut escaped = String::new();
+        for c in text.chars() {
+            match c {
+                '(' | ')' | '\\' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                ' '..='~' => escaped.push(c),
+                '\u{a0}'..='\u{ff}' => {
+                    let _ = write!(escaped, "\\{:03o}", c as u32);
+                }
+                _ => escaped.push('?'),
+            }
+        }
+
+        escaped
+    }
+
+    /// A page drawn with its origin in the top left corner, y grows downwards
+    pub struct Page {
+        width: f32,
+        height: f32,
+        content: String,
+    }
+
+    impl Page {
+        pub fn new((width, height): (f32, f32)) -> Self {
+            Self {
+                width,
+                height,
+                content: String::new(),
+            }
+        }
+
+        pub fn width(&self) -> f32 {
+            self.width
+        }
+
+        pub fn height(&self) -> f32 {
+            self.height
+        }
+
+        /// Writes text with its baseline at `y`
+        pub fn text(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str) {
+            let font = match font {
+                Font::Regular => "F1",
+                Font::Bold => "F2",
+            };
+            let _ = writeln!(
+                self.content,
+                "BT /{font} {size:.2} Tf {x:.2} {:.2} Td ({}) Tj ET",
+                self.height - y,
+                escape(text)
+            );
+        }
+
+        /// Sets the colour used for both lines and fills, 0 is black and 1 is white
+        pub fn grey(&mut self, level: f32) {
+            let _ = writeln!(self.content, "{level:.2} g {level:.2} G");
+        }
+
+        pub fn line(&mut self, from: (f32, f32), to: (f32, f32), width: f32) {
+            self.polyline(&[from, to], width);
+        }
+
+        pub fn polyline(&mut self, points: &[(f32, f32)], width: f32) {
+            let Some(((x, y), rest)) = points.split_first() else {
+                return;
+            };
+
+            let _ = write!(self.content, "{width:.2} w {x:.2} {:.2} m", self.height - y);
+            for (x, y) in rest {
+                let _ = write!(self.content, " {x:.2} {:.2} l", self.height - y);
+            }
+            self.content.push_str(" S\n");
+        }
+
+        /// A rectangle with its top left corner at `x`, `y`
+        pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, fill: bool) {
+            let _ = writeln!(
+                self.content,
+                "0.5 w {x:.2} {:.2} {width:.2} {height:.2} re {}",
+                self.height - y - height,
+                match fill {
+                    true => "f",
+                    false => "S",
+                }
+            );
+        }
+    }
+
+    #[derive(Default)]
+    pub struct Document {
+        pages: Vec<Page>,
+    }
+
+    impl Document {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn push(&mut self, page: Page) {
+            self.pages.push(page);
+        }
+
+        pub fn render(&self) -> Vec<u8> {
+            // Objects 1 to 4 are the catalog, page tree and fonts, each page then takes two
+            // objects for itself and its content stream
+            let mut objects = vec![
+                "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+                format!(
+                    "<< /Type /Pages /Kids [{}] /Count {} >>",
+                    (0..self.pages.len())
+                        .map(|f| format!("{} 0 R", 5 + f * 2))
+                        .collect::<Vec<String>>()
+                        .join(" "),
+                    self.pages.len()
+                ),
+                "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+                    .to_string(),
+                "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+                    .to_string(),
+            ];
+            for (index, page) in self.pages.iter().enumerate() {
+                objects.push(format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                     /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    page.width,
+                    page.height,
+                    6 + index * 2
+                ));
+                objects.push(format!(
+                    "<< /Length {} >>\nstream\n{}endstream",
+                    page.content.len(),
+                    page.content
+                ));
+            }
+
+            let mut out = String::from("%PDF-1.4\n");
+            let mut offsets = vec![];
+            for (index, object) in objects.iter().enumerate() {
+                offsets.push(out.len());
+                let _ = writeln!(out, "{} 0 obj\n{}\nendobj", index + 1, object);
+            }
+
+            let xref = out.len();
+            let _ = writeln!(out, "xref\n0 {}\n0000000000 65535 f ", objects.len() + 1);
+            for offset in offsets {
+                let _ = writeln!(out, "{offset:010} 00000 n ");
+            }
+            let _ = writeln!(
+                out,
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF",
+                objects.len() + 1,
+                xref
+            );
+
+            out.into_bytes()
+        }
+    }
+}
+
+/// Printable reports for commanding officers
+pub mod reports {
+    use super::pdf::{self, Document, Font, Page};
+    use crate::database::data::{self, Criteria, Exemption, Inspection, Tenant, Unit, User};
+
+    const MARGIN: f32 = 50.0;
+
+    /// Lays out lines of a report down the page, starting a new page when one fills up
+    struct Writer {
+        document: Document,
+        page: Page,
+        y: f32,
+    }
+
+    impl Writer {
+        fn new(title: &str, subtitle: &str) -> Self {
+            let mut writer = Self {
+                document: Document::new(),
+                page: Page::new(pdf::LETTER),
+                y: MARGIN,
+            };
+            writer.y += 18.0;
+            writer.page.text(MARGIN, writer.y, 18.0, Font::Bold, title);
+            writer.y += 16.0;
+            writer
+                .page
+                .text(MARGIN, writer.y, 10.0, Font::Regular, subtitle);
+            writer.y += 10.0;
+            writer.rule();
+
+            writer
+        }
+
+        fn content_width(&self) -> f32 {
+            self.page.width() - MARGIN * 2.0
+        }
+
+        /// Starts a new page unless `height` still fits on this one
+        fn reserve(&mut self, height: f32) {
+            if self.y + height > self.page.height() - MARGIN {
+                let page = std::mem::replace(&mut self.page, Page::new(pdf::LETTER));
+                self.document.push(page);
+                self.y = MARGIN;
+            }
+        }
+
+        fn rule(&mut self) {
+            self.y += 6.0;
+            let width = self.page.width();
+            self.page
+                .line((MARGIN, self.y), (width - MARGIN, self.y), 0.5);
+            self.y += 6.0;
+        }
+
+        fn heading(&mut self, text: &str) {
+            self.reserve(40.0);
+            self.y += 20.0;
+            self.page.text(MARGIN, self.y, 13.0, Font::Bold, text);
+            self.y += 4.0;
+        }
+
+        /// Writes one line of cells, each starting at its offset from the margin and cut to fit
+        /// before the next one
+        fn row(&mut self, cells: &[(f32, &str)], font: Font) {
+            self.reserve(14.0);
+            self.y += 14.0;
+            for (index, (x, text)) in cells.iter().enumerate() {
+                let end = cells.get(index + 1).map_or(self.content_width(), |f| f.0);
+                self.page.text(
+                    MARGIN + x,
+                    self.y,
+                    10.0,
+                    font,
+                    &pdf::fit(text, end - x - 6.0, 10.0),
+                );
+            }
+        }
+
+        fn paragraph(&mut self, text: &str, indent: f32) {
+            for line in pdf::wrap(text, self.content_width() - indent, 10.0) {
+                self.reserve(13.0);
+                self.y += 13.0;
+                self.page
+                    .text(MARGIN + indent, self.y, 10.0, Font::Regular, &line);
+            }
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            self.document.push(self.page);
+            self.document.render()
+        }
+    }
+
+    fn date(timestamp: Option<i64>) -> String {
+        match timestamp.and_then(|f| chrono::DateTime::from_timestamp(f, 0)) {
+            Some(t) => t.format("%Y-%m-%d").to_string(),
+            None => "-".into(),
+        }
+    }
+
+    fn percentage(value: Option<f32>) -> String {
+        match value {
+            Some(t) => format!("{t:.1}%"),
+            None => "-".into(),
+        }
+    }
+
+    fn unit_name(units: &[Unit], id: &Option<String>) -> String {
+        match id {
+            Some(id) => units
+                .iter()
+                .find(|f| &f.id == id)
+                .map_or(id.clone(), |f| f.name.clone()),
+            None => "-".into(),
+        }
+    }
+
+    fn chronological(user: &User) -> Vec<&Inspection> {
+        let mut inspections: Vec<&Inspection> = user.counted_inspections().collect();
+        inspections.sort_by_key(|f| f.date.unwrap_or(0));
+
+        inspections
+    }
+
+    /// How a criterion is written in the report, e.g. "Pass" or "3 / 4"
+    fn criterion_result(criteria: &Criteria) -> String {
+        match (criteria, criteria.exemption()) {
+            (_, Some(Exemption::NotApplicable)) => "N/A".into(),
+            (_, Some(Exemption::Excused)) => "Excused".into(),
+            (Criteria::PassFail(t), None) => match t.state {
+                Some(true) => "Pass".into(),
+                Some(false) => "Fail".into(),
+                None => "Not marked".into(),
+            },
+            (Criteria::Graded(t), None) => {
+                format!("{} / {}", t.state.unwrap_or(0), t.description.len())
+            }
+            (Criteria::Comment(_), None) => "".into(),
+        }
+    }
+
+    fn criterion_note(criteria: &Criteria) -> Option<&str> {
+        match criteria {
+            Criteria::PassFail(t) => t.note.as_deref(),
+            Criteria::Graded(t) => t.note.as_deref(),
+            Criteria::Comment(t) => t.as_deref(),
+        }
+    }
+
+    /// Plots percentages in order as a line chart `height` points tall across the page
+    fn history_chart(writer: &mut Writer, scores: &[f32], height: f32) {
+        writer.reserve(height + 20.0);
+        writer.y += 10.0;
+
+        let (left, top) = (MARGIN + 30.0, writer.y);
+        let width = writer.content_width() - 30.0;
+        let page = &mut writer.page;
+
+        page.grey(0.85);
+        for percent in [25.0, 50.0, 75.0] {
+            let y = top + height * (1.0 - percent / 100.0);
+            page.line((left, y), (left + width, y), 0.5);
+        }
+        page.grey(0.0);
+        page.rect(left, top, width, height, false);
+        for (label, percent) in [("100%", 100.0), ("50%", 50.0), ("0%", 0.0)] {
+            let y = top + height * (1.0 - percent / 100.0) + 3.0;
+            page.text(MARGIN, y, 8.0, Font::Regular, label);
+        }
+
+        let step = match scores.len() {
+            0 | 1 => 0.0,
+            n => width / (n - 1) as f32,
+        };
+        let points: Vec<(f32, f32)> = scores
+            .iter()
+            .enumerate()
+            .map(|(index, score)| {
+                (
+                    left + step * index as f32 + if scores.len() == 1 { width / 2.0 } else { 0.0 },
+                    top + height * (1.0 - score.clamp(0.0, 100.0) / 100.0),
+                )
+            })
+            .collect();
+        page.polyline(&points, 1.5);
+        for (x, y) in points.iter() {
+            page.rect(x - 2.0, y - 2.0, 4.0, 4.0, true);
+        }
+
+        writer.y += height;
+    }
+
+    pub fn cadet_report(user: &User, tenant: &Tenant, units: &[Unit]) -> Vec<u8> {
+        let mut writer = Writer::new(
+            &format!("Cadet Report: {}", user.display_name()),
+            &format!(
+                "{} - generated {}",
+                tenant.name,
+                date(Some(chrono::Utc::now().timestamp()))
+            ),
+        );
+
+        writer.heading("Profile");
+        let profile = [
+            ("Rank", user.profile.rank.clone().unwrap_or("-".into())),
+            ("Flight", unit_name(units, &user.flight)),
+            (
+                "Service number",
+                user.profile.service_number.clone().unwrap_or("-".into()),
+            ),
+            ("Status", format!("{:?}", user.profile.status)),
+            ("Date joined", date(user.profile.date_joined)),
+            ("Attendance", percentage(user.attendance_percentage())),
+        ];
+        for (label, value) in profile.iter() {
+            writer.row(&[(0.0, label), (120.0, value)], Font::Regular);
+        }
+
+        let inspections = chronological(user);
+        let scores: Vec<f32> = inspections
+            .iter()
+            .filter_map(|f| f.get_score().percentage())
+            .collect();
+
+        writer.heading("Inspection History");
+        match scores.is_empty() {
+            true => writer.paragraph("No scored inspections yet.", 0.0),
+            false => history_chart(&mut writer, &scores, 140.0),
+        }
+
+        writer.y += 6.0;
+        writer.row(
+            &[
+                (0.0, "Date"),
+                (90.0, "Inspection"),
+                (330.0, "Score"),
+                (420.0, "Percentage"),
+            ],
+            Font::Bold,
+        );
+        for inspection in inspections.iter().rev() {
+            let score = inspection.get_score();
+            writer.row(
+                &[
+                    (0.0, &date(inspection.date)),
+                    (90.0, &inspection.name),
+                    (330.0, &format!("{} / {}", score.score(), score.out_of())),
+                    (420.0, &percentage(score.percentage())),
+                ],
+                Font::Regular,
+            );
+        }
+
+        if let Some(latest) = inspections.last() {
+            writer.heading(&format!(
+                "Latest Inspection: {} ({})",
+                latest.name,
+                date(latest.date)
+            ));
+            for criteria in latest.criteria.iter() {
+                if let Some(name) = criteria.category_name() {
+                    writer.row(
+                        &[
+                            (0.0, name),
+                            (250.0, &criterion_result(criteria)),
+                            (350.0, &criteria.deficiency_codes().join(", ")),
+                        ],
+                        Font::Regular,
+                    );
+                }
+                if let Some(note) = criterion_note(criteria) {
+                    writer.paragraph(note, 15.0);
+                }
+            }
+        }
+
+        writer.finish()
+    }
+
+    pub fn flight_report(unit: &Unit, cadets: &[User], tenant: &Tenant, units: &[Unit]) -> Vec<u8> {
+        let mut writer = Writer::new(
+            &format!("Flight Report: {}", unit.name),
+            &format!(
+                "{} - generated {}",
+                tenant.name,
+                date(Some(chrono::Utc::now().timestamp()))
+            ),
+        );
+
+        let latest: Vec<Option<&Inspection>> =
+            cadets.iter().map(|f| chronological(f).pop()).collect();
+        let scores: Vec<f32> = latest
+            .iter()
+            .filter_map(|f| f.and_then(|t| t.get_score().percentage()))
+            .collect();
+
+        writer.heading("Summary");
+        let average = match scores.len() {
+            0 => None,
+            n => Some(scores.iter().sum::<f32>() / n as f32),
+        };
+        let summary = [
+            ("Cadets", cadets.len().to_string()),
+            ("Inspected", scores.len().to_string()),
+            ("Average latest score", percentage(average)),
+        ];
+        for (label, value) in summary.iter() {
+            writer.row(&[(0.0, label), (150.0, value)], Font::Regular);
+        }
+
+        writer.heading("Cadets");
+        writer.row(
+            &[
+                (0.0, "Name"),
+                (170.0, "Rank"),
+                (250.0, "Section"),
+                (330.0, "Last inspected"),
+                (420.0, "Score"),
+                (470.0, "Attendance"),
+            ],
+            Font::Bold,
+        );
+        for (cadet, inspection) in cadets.iter().zip(latest.iter()) {
+            let section = match cadet.flight.as_ref() == Some(&unit.id) {
+                true => "-".into(),
+                false => unit_name(units, &cadet.flight),
+            };
+            writer.row(
+                &[
+                    (0.0, &cadet.display_name()),
+                    (170.0, cadet.profile.rank.as_deref().unwrap_or("-")),
+                    (250.0, &section),
+                    (330.0, &date(inspection.and_then(|f| f.date))),
+                    (
+                        420.0,
+                        &percentage(inspection.and_then(|f| f.get_score().percentage())),
+                    ),
+                    (470.0, &percentage(cadet.attendance_percentage())),
+                ],
+                Font::Regular,
+            );
+        }
+
+        writer.finish()
+    }
+
+    /// Active cadets in a unit and its sections, ordered by name
+    pub fn unit_cadets(
+        tenant: &str,
+        unit: &str,
+        units: &[Unit],
+    ) -> Result<Vec<User>, std::io::Error> {
+        let mut cadets: Vec<User> = data::read_tenant_users(tenant)?
+            .into_iter()
+            .filter(|f| f.archived.is_none() && f.profile.status == data::CadetStatus::Active)
+            .filter(|f| {
+                f.flight
+                    .as_ref()
+                    .is_some_and(|t| data::unit_within(units, t, unit))
+            })
+            .collect();
+        cadets.sort_by_key(|f| f.display_name());
+
+        Ok(cadets)
+    }
+}
+
+/// The same drawing operations as [`pdf::Page`] written out as an SVG document
+pub mod svg {
+    use std::fmt::Write;
+
+    use super::pdf::Font;
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    pub struct Page {
+        width: f32,
+        height: f32,
+        content: String,
+        fill: String,
+    }
+
+    impl Page {
+        pub fn new((width, height): (f32, f32)) -> Self {
+            Self {
+                width,
+                height,
+                content: String::new(),
+                fill: "#000".into(),
+            }
+        }
+
+        pub fn text(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str) {
+            let _ = writeln!(
+                self.content,
+                r#"<text x="{x:.2}" y="{y:.2}" font-family="Helvetica, Arial, sans-serif" font-size="{size:.2}" font-weight="{}" fill="{}">{}</text>"#,
+                match font {
+                    Font::Regular => "normal",
+                    Font::Bold => "bold",
+                },
+                self.fill,
+                escape(text)
+            );
+        }
+
+        /// Sets the colour used for both lines and fills, 0 is black and 1 is white
+        pub fn grey(&mut self, level: f32) {
+            let level = (level.clamp(0.0, 1.0) * 255.0).round() as u8;
+            self.fill = format!("#{level:02x}{level:02x}{level:02x}");
+        }
+
+        pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, fill: bool) {
+            let _ = writeln!(
+                self.content,
+                r#"<rect x="{x:.2}" y="{y:.2}" width="{width:.2}" height="{height:.2}" {}/>"#,
+                match fill {
+                    true => format!(r#"fill="{}""#, self.fill),
+                    false => format!(r#"fill="none" stroke="{}" stroke-width="0.5""#, self.fill),
+                }
+            );
+        }
+
+        /// Sized in points so the page prints at its real size
+        pub fn render(&self) -> String {
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="{w}pt" height="{h}pt" viewBox="0 0 {w} {h}">
+{}</svg>
+"#,
+                self.content,
+                w = self.width,
+                h = self.height,
+            )
+        }
+    }
+}
+
+/// Sheets of wallet cards with a cadet's QR code, printed onto label stock
+pub mod cards {
+    use serde::Deserialize;
+
+    use super::pdf::{self, Font};
+    use super::svg;
+    use crate::database::data::{self, Tenant, Unit, User};
+
+    /// Label stock measurements in points, from the top left corner of the sheet
+    #[derive(Deserialize, Debug, Clone, Copy)]
+    pub struct Layout {
+        pub columns: usize,
+        pub rows: usize,
+        pub card_width: f32,
+        pub card_height: f32,
+        /// Distance from the left edge of the sheet to the first column
+        pub left: f32,
+        /// Distance from the top edge of the sheet to the first row
+        pub top: f32,
+        /// Distance from the left of one card to the left of the next
+        pub horizontal_pitch: f32,
+        /// Distance from the top of one card to the top of the next
+        pub vertical_pitch: f32,
+    }
+
+    #[derive(Deserialize, Debug, Clone, Copy, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Preset {
+        /// Business cards, 2 by 5 of 3.5" by 2"
+        #[default]
+        Avery5371,
+        /// Address labels, 3 by 10 of 2.625" by 1"
+        Avery5160,
+        /// Shipping labels, 2 by 5 of 4" by 2"
+        Avery5163,
+    }
+
+    impl From<Preset> for Layout {
+        fn from(value: Preset) -> Self {
+            match value {
+                Preset::Avery5371 => Layout {
+                    columns: 2,
+                    rows: 5,
+                    card_width: 252.0,
+                    card_height: 144.0,
+                    left: 54.0,
+                    top: 36.0,
+                    horizontal_pitch: 252.0,
+                    vertical_pitch: 144.0,
+                },
+                Preset::Avery5160 => Layout {
+                    columns: 3,
+                    rows: 10,
+                    card_width: 189.0,
+                    card_height: 72.0,
+                    left: 13.5,
+                    top: 36.0,
+                    horizontal_pitch: 198.0,
+                    vertical_pitch: 72.0,
+                },
+                Preset::Avery5163 => Layout {
+                    columns: 2,
+                    rows: 5,
+                    card_width: 288.0,
+                    card_height: 144.0,
+                    left: 11.25,
+                    top: 36.0,
+                    horizontal_pitch: 301.5,
+                    vertical_pitch: 144.0,
+                },
+            }
+        }
+    }
+
+    /// Either the name of a preset or the measurements of custom stock
+    #[derive(Deserialize, Debug, Clone, Copy)]
+    #[serde(untagged)]
+    pub enum LayoutChoice {
+        Preset(Preset),
+        Custom(Layout),
+    }
+
+    impl Default for LayoutChoice {
+        fn default() -> Self {
+            LayoutChoice::Preset(Preset::default())
+        }
+    }
+
+    impl Layout {
+        /// Checks that the cards fit on a sheet of letter paper without overlapping
+        pub fn validate(&self) -> Result<(), String> {
+            let (width, height) = pdf::LETTER;
+            if self.columns == 0 || self.rows == 0 {
+                return Err("A layout needs at least one row and column".into());
+            }
+            let sizes = [
+                self.card_width,
+                self.card_height,
+                self.left,
+                self.top,
+                self.horizontal_pitch,
+                self.vertical_pitch,
+            ];
+            if sizes.iter().any(|f| !f.is_finite()) {
+                return Err("Every size must be a number".into());
+            }
+            if self.left < 0.0 || self.top < 0.0 {
+                return Err("Cards must start on the page".into());
+            }
+            if self.card_width < 36.0 || self.card_height < 36.0 {
+                return Err("Cards must be at least half an inch on each side".into());
+            }
+            // The QR code is as tall as the card, the text goes beside it
+            if self.card_width - self.card_height < 36.0 {
+                return Err("Cards must be at least half an inch wider than they are tall".into());
+            }
+            if self.horizontal_pitch < self.card_width || self.vertical_pitch < self.card_height {
+                return Err("Cards would overlap".into());
+            }
+            if self.left + self.horizontal_pitch * (self.columns - 1) as f32 + self.card_width
+                > width
+                || self.top + self.vertical_pitch * (self.rows - 1) as f32 + self.card_height
+                    > height
+            {
+                return Err("Cards do not fit on the page".into());
+            }
+
+            Ok(())
+        }
+
+        pub fn per_page(&self) -> usize {
+            self.columns * self.rows
+        }
+
+        /// Top left corner of the card at `index` on its page
+        fn position(&self, index: usize) -> (f32, f32) {
+            let index = index % self.per_page();
+            (
+                self.left + self.horizontal_pitch * (index % self.columns) as f32,
+                self.top + self.vertical_pitch * (index / self.columns) as f32,
+            )
+        }
+    }
+
+    /// The drawing operations a card needs, so one layout can be written as PDF or SVG
+    trait Canvas {
+        fn text(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str);
+        fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, fill: bool);
+        fn grey(&mut self, level: f32);
+    }
+
+    impl Canvas for pdf::Page {
+        fn text(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str) {
+            pdf::Page::text(self, x, y, size, font, text)
+        }
+        fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, fill: bool) {
+            pdf::Page::rect(self, x, y, width, height, fill)
+        }
+        fn grey(&mut self, level: f32) {
+            pdf::Page::grey(self, level)
+        }
+    }
+
+    impl Canvas for svg::Page {
+        fn text(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str) {
+            svg::Page::text(self, x, y, size, font, text)
+        }
+        fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, fill: bool) {
+            svg::Page::rect(self, x, y, width, height, fill)
+        }
+        fn grey(&mut self, level: f32) {
+            svg::Page::grey(self, level)
+        }
+    }
+
+    /// What is printed on one card
+    pub struct Card {
+        pub url: String,
+        pub name: String,
+        pub flight: Option<String>,
+        pub claim_code: Option<String>,
+    }
+
+    impl Card {
+        pub fn new(user: &User, tenant: &Tenant, units: &[Unit]) -> Self {
+            Self {
+                url: tenant.cadet_url(&data::card_code(user)),
+                name: user.display_name(),
+                flight: user.flight.as_ref().map(|id| {
+                    units
+                        .iter()
+                        .find(|f| &f.id == id)
+                        .map_or(id.clone(), |f| f.name.clone())
+                }),
+                claim_code: user.claim_code.clone(),
+            }
+        }
+
+        fn draw(&self, canvas: &mut impl Canvas, layout: &Layout, (x, y): (f32, f32), title: &str) {
+            let padding = (layout.card_height * 0.08).min(10.0);
+
+            // Light outline so cards can still be cut apart when printed on plain paper
+            canvas.grey(0.8);
+            canvas.rect(x, y, layout.card_width, layout.card_height, false);
+            canvas.grey(0.0);
+
+            let qr_size = layout.card_height - padding * 2.0;
+            if let Ok(code) =
+                qrcode::QrCode::with_error_correction_level(self.url.as_bytes(), qrcode::EcLevel::M)
+            {
+                let modules = code.width();
+                // Leaves a two module quiet zone inside the square
+                let module = qr_size / (modules + 4) as f32;
+                for (index, colour) in code.to_colors().iter().enumerate() {
+                    if *colour == qrcode::Color::Dark {
+                        canvas.rect(
+                            x + padding + module * ((index % modules) as f32 + 2.0),
+                            y + padding + module * ((index / modules) as f32 + 2.0),
+                            module,
+                            module,
+                            true,
+                        );
+                    }
+                }
+            }
+
+            let text_x = x + padding + qr_size + padding;
+            let text_width = layout.card_width - (text_x - x) - padding;
+            let size = (layout.card_height / 10.0).clamp(6.0, 12.0);
+            let mut line = y + padding + size;
+
+            canvas.text(
+                text_x,
+                line,
+                size,
+                Font::Bold,
+                &pdf::fit(&self.name, text_width, size),
+            );
+            if let Some(flight) = &self.flight {
+                line += size * 1.3;
+                canvas.text(
+                    text_x,
+                    line,
+                    size * 0.85,
+                    Font::Regular,
+                    &pdf::fit(flight, text_width, size * 0.85),
+                );
+            }
+            if let Some(code) = &self.claim_code {
+                line += size * 1.6;
+                canvas.text(text_x, line, size * 0.75, Font::Regular, "Claim code");
+                line += size * 1.2;
+                canvas.text(text_x, line, size * 1.1, Font::Bold, code);
+            }
+            canvas.text(
+                text_x,
+                y + layout.card_height - padding,
+                size * 0.7,
+                Font::Regular,
+                &pdf::fit(title, text_width, size * 0.7),
+            );
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Format {
+        #[default]
+        Pdf,
+        Svg,
+    }
+
+    /// Every card over as many pages as needed
+    pub fn pdf_sheet(cards: &[Card], layout: &Layout, title: &str) -> Vec<u8> {
+        let mut document = pdf::Document::new();
+        for chunk in cards.chunks(layout.per_page()) {
+            let mut page = pdf::Page::new(pdf::LETTER);
+            for (index, card) in chunk.iter().enumerate() {
+                card.draw(&mut page, layout, layout.position(index), title);
+            }
+            document.push(page);
+        }
+
+        document.render()
+    }
+
+    /// One page of cards, SVG has no notion of pages so each has to be requested separately
+    pub fn svg_sheet(cards: &[Card], layout: &Layout, title: &str, page: usize) -> String {
+        let mut sheet = svg::Page::new(pdf::LETTER);
+        for (index, card) in cards
+            .iter()
+            .enumerate()
+            .skip(page * layout.per_page())
+            .take(layout.per_page())
+        {
+            card.draw(&mut sheet, layout, layout.position(index), title);
+        }
+
+        sheet.render()
+    }
+}
+
+/// QR codes rendered as SVG or PNG at a requested size, optionally with a logo in the centre
+pub mod qr {
+    use std::io::{Cursor, Error, ErrorKind};
+    use std::sync::Mutex;
+
+    use base64::Engine;
+    use crypto::digest::Digest;
+    use image::{imageops, ImageFormat, Rgba, RgbaImage};
+    use qrcode::{Color, EcLevel, QrCode};
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Format {
+        Svg,
+        Png,
+    }
+
+    #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ErrorCorrection {
+        #[default]
+        L,
+        M,
+        Q,
+        H,
+    }
+
+    impl From<ErrorCorrection> for EcLevel {
+        fn from(value: ErrorCorrection) -> Self {
+            match value {
+                ErrorCorrection::L => EcLevel::L,
+                ErrorCorrection::M => EcLevel::M,
+                ErrorCorrection::Q => EcLevel::Q,
+                ErrorCorrection::H => EcLevel::H,
+            }
+        }
+    }
+
+    fn size() -> u32 {
+        300
+    }
+
+    fn margin() -> u32 {
+        4
+    }
+
+    #[derive(Deserialize, Debug, Clone, Copy)]
+    pub struct Options {
+        /// Smallest width and height of the image, in pixels for PNG and user units for SVG
+        #[serde(default = "size")]
+        pub size: u32,
+        /// Width of the blank border, in modules
+        #[serde(default = "margin")]
+        pub margin: u32,
+        #[serde(default)]
+        pub ec: ErrorCorrection,
+        /// Places the squadron logo in the centre, this always uses the highest error correction
+        /// so the code still scans with the modules under the logo covered
+        #[serde(default)]
+        pub logo: bool,
+    }
+
+    /// Share of the code's width taken by the logo, small enough for level H to recover
+    const LOGO_SCALE: f32 = 0.22;
+    const CACHE_ENTRIES: usize = 64;
+
+    impl Options {
+        pub fn validate(&self) -> Result<(), String> {
+            if !(64..=2048).contains(&self.size) {
+                return Err("Size must be between 64 and 2048".into());
+            }
+            if self.margin > 16 {
+                return Err("Margin must be at most 16 modules".into());
+            }
+
+            Ok(())
+        }
+
+        fn ec_level(&self) -> EcLevel {
+            match self.logo {
+                true => EcLevel::H,
+                false => self.ec.into(),
+            }
+        }
+    }
+
+    pub struct Rendered {
+        pub etag: String,
+        pub content_type: &'static str,
+        pub body: Vec<u8>,
+    }
+
+    static CACHE: Mutex<Vec<(String, std::sync::Arc<Rendered>)>> = Mutex::new(vec![]);
+
+    /// Identifies the image that would be rendered, so unchanged codes can be answered with a
+    /// `304 Not Modified` or served from the cache
+    pub fn etag(data: &str, format: Format, options: &Options, logo: Option<&[u8]>) -> String {
+        let mut hasher = crypto::sha2::Sha256::new();
+        hasher.input_str(&format!(
+            "{data}|{format:?}|{}|{}|{:?}|{}",
+            options.size, options.margin, options.ec, options.logo
+        ));
+        if let Some(t) = logo.filter(|_| options.logo) {
+            hasher.input(t);
+        }
+
+        format!("\"{}\"", &hasher.result_str()[..32])
+    }
+
+    /// Renders the code, reusing the last result for the same inputs
+    pub fn render(
+        data: &str,
+        format: Format,
+        options: &Options,
+        logo: Option<&[u8]>,
+    ) -> Result<std::sync::Arc<Rendered>, Error> {
+        let etag = etag(data, format, options, logo);
+        if let Some((_, t)) = CACHE
+            .lock()
+            .expect("QR cache lock poisoned")
+            .iter()
+            .find(|(f, _)| *f == etag)
+        {
+            return Ok(t.clone());
+        }
+
+        let code = QrCode::with_error_correction_level(data.as_bytes(), options.ec_level())
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+        let logo = logo.filter(|_| options.logo);
+        let rendered = std::sync::Arc::new(match format {
+            Format::Svg => Rendered {
+                etag: etag.clone(),
+                content_type: "image/svg+xml",
+                body: svg(&code, options, logo).into_bytes(),
+            },
+            Format::Png => Rendered {
+                etag: etag.clone(),
+                content_type: "image/png",
+                body: png(&code, options, logo)?,
+            },
+        });
+
+        let mut cache = CACHE.lock().expect("QR cache lock poisoned");
+        if cache.len() >= CACHE_ENTRIES {
+            cache.remove(0);
+        }
+        cache.push((etag, rendered.clone()));
+
+        Ok(rendered)
+    }
+
+    /// Side of one module in output units, rounded up so the code is at least `size` across
+    fn module_size(code: &QrCode, options: &Options) -> u32 {
+        let modules = code.width() as u32 + options.margin * 2;
+        options.size.div_ceil(modules)
+    }
+
+    fn svg(code: &QrCode, options: &Options, logo: Option<&[u8]>) -> String {
+        let module = module_size(code, options);
+        let width = code.width() as u32;
+        let side = module * (width + options.margin * 2);
+
+        let mut path = String::new();
+        for (index, colour) in code.to_colors().iter().enumerate() {
+            if *colour == Color::Dark {
+                let x = (index as u32 % width + options.margin) * module;
+                let y = (index as u32 / width + options.margin) * module;
+                path.push_str(&format!("M{x} {y}h{module}v{module}h-{module}z"));
+            }
+        }
+
+        let logo = match logo {
+            Some(bytes) => {
+                let logo_side = (width * module) as f32 * LOGO_SCALE;
+                let padding = module as f32;
+                let start = (side as f32 - logo_side) / 2.0;
+                format!(
+                    r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="white"/><image x="{start:.1}" y="{start:.1}" width="{logo_side:.1}" height="{logo_side:.1}" href="data:image/png;base64,{}"/>"#,
+                    start - padding,
+                    start - padding,
+                    logo_side + padding * 2.0,
+                    logo_side + padding * 2.0,
+                    base64::engine::general_purpose::STANDARD.encode(bytes)
+                )
+            }
+            None => String::new(),
+        };
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="{side}" height="{side}" viewBox="0 0 {side} {side}" shape-rendering="crispEdges"><rect width="{side}" height="{side}" fill="white"/><path d="{path}" fill="black"/>{logo}</svg>
+"#
+        )
+    }
+
+    fn png(code: &QrCode, options: &Options, logo: Option<&[u8]>) -> Result<Vec<u8>, Error> {
+        let module = module_size(code, options);
+        let width = code.width() as u32;
+        let side = module * (width + options.margin * 2);
+
+        let colours = code.to_colors();
+        let mut image = RgbaImage::from_fn(side, side, |x, y| {
+            let (x, y) = (x / module, y / module);
+            let dark = x >= options.margin
+                && y >= options.margin
+                && x < width + options.margin
+                && y < width + options.margin
+                && colours[((y - options.margin) * width + x - options.margin) as usize]
+                    == Color::Dark;
+            match dark {
+                true => Rgba([0, 0, 0, 255]),
+                false => Rgba([255, 255, 255, 255]),
+            }
+        });
+
+        if let Some(bytes) = logo {
+            let logo = image::load_from_memory_with_format(bytes, ImageFormat::Png)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            let logo_side = ((width * module) as f32 * LOGO_SCALE) as u32;
+            let logo = logo
+                .resize(logo_side, logo_side, imageops::FilterType::Lanczos3)
+                .to_rgba8();
+
+            // White backing so the logo does not blend into the modules around it
+            let padding = module;
+            let backing = RgbaImage::from_pixel(
+                logo.width() + padding * 2,
+                logo.height() + padding * 2,
+                Rgba([255, 255, 255, 255]),
+            );
+            let x = (side - backing.width()) / 2;
+            let y = (side - backing.height()) / 2;
+            imageops::overlay(&mut image, &backing, x.into(), y.into());
+            imageops::overlay(
+                &mut image,
+                &logo,
+                (x + padding).into(),
+                (y + padding).into(),
+            );
+        }
+
+        let mut out = Cursor::new(vec![]);
+        image
+            .write_to(&mut out, ImageFormat::Png)
+            .map_err(Error::other)?;
+
+        Ok(out.into_inner())
+    }
+}